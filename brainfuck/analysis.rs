@@ -0,0 +1,304 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use super::{Command, Position};
+use super::Command::*;
+use super::config::TapeConfig;
+
+/* diagnostics */
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Lint {
+	// a loop entered when its cell is always zero, like a comment loop at the start of a program
+	DeadLoop,
+	// a loop that changes neither the pointer nor its own cell, so it can't end once entered
+	InfiniteLoop,
+	// a move to the left of the first cell
+	NegativePointer
+}
+
+impl fmt::Display for Lint {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Lint::DeadLoop => write!(f, "this loop is never run, as its cell is always zero here"),
+			Lint::InfiniteLoop => write!(f, "this loop never ends once entered, as it doesn't change its cell"),
+			Lint::NegativePointer => write!(f, "the pointer moves to the left of the first cell here")
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+	pub lint: Lint,
+	// the instruction positions of the first and the last instruction involved
+	pub start: usize,
+	pub end: usize
+}
+
+impl Diagnostic {
+	// `positions` maps instruction positions to the source, see instruction_positions
+	pub fn span(&self, positions: &[Position]) -> (Position, Position) {
+		(positions[self.start], positions[self.end])
+	}
+}
+
+// one line per diagnostic, starting with its source span
+pub fn report(diagnostics: &[Diagnostic], positions: &[Position]) -> String {
+	let mut report = String::new();
+
+	for diagnostic in diagnostics {
+		let (start, end) = diagnostic.span(positions);
+		report.push_str(&format!("{}:{}-{}:{}: {}\n", start.line, start.column, end.line, end.column, diagnostic.lint));
+	}
+
+	report
+}
+
+/* analysis */
+
+// finds what is certain to go wrong (or to be useless) whenever the code involved is reached,
+// without running the program; programs are assumed to start on the first cell of a blank tape
+pub fn analyze(program: &[Command], config: &TapeConfig) -> Vec<Diagnostic> {
+	let mut analyzer = Analyzer { config: config, diagnostics: Vec::new() };
+	analyzer.analyze(program, 0, &mut State::start());
+	analyzer.diagnostics.sort_by_key(|diagnostic| diagnostic.start);
+
+	analyzer.diagnostics
+}
+
+// what is known about the tape at some point of the program
+#[derive(Debug, Clone)]
+struct State {
+	// counted from the first cell if `anchored`, or else from wherever the pointer was when the
+	// analysis lost track of it
+	pos: isize,
+	anchored: bool,
+	// the cells that were changed, with their values if they are known
+	values: HashMap<isize, Option<i64>>,
+	// whether the cells that weren't changed are known to still be zero
+	blank: bool
+}
+
+impl State {
+	fn start() -> State {
+		State { pos: 0, anchored: true, values: HashMap::new(), blank: true }
+	}
+
+	fn unknown() -> State {
+		State { pos: 0, anchored: false, values: HashMap::new(), blank: false }
+	}
+
+	fn cell(&self) -> Option<i64> {
+		match self.values.get(&self.pos) {
+			Some(&value) => value,
+			None => if self.blank { Some(0) } else { None }
+		}
+	}
+
+	fn set_cell(&mut self, value: Option<i64>) {
+		self.values.insert(self.pos, value);
+	}
+}
+
+struct Analyzer<'a> {
+	config: &'a TapeConfig,
+	diagnostics: Vec<Diagnostic>
+}
+
+impl<'a> Analyzer<'a> {
+	fn report(&mut self, lint: Lint, start: usize, end: usize) {
+		self.diagnostics.push(Diagnostic { lint: lint, start: start, end: end });
+	}
+
+	// returns the instruction position following the commands
+	fn analyze(&mut self, commands: &[Command], mut pc: usize, state: &mut State) -> usize {
+		for command in commands {
+			match *command {
+				IncrementPos => state.pos += 1,
+				DecrementPos => {
+					state.pos -= 1;
+					if state.anchored && state.pos < 0 && !self.config.wrap_pointer {
+						self.report(Lint::NegativePointer, pc, pc);
+						// the program would stop here, so there's nothing more to tell about what follows
+						state.anchored = false;
+					}
+				},
+				IncrementByte => {
+					let value = state.cell().and_then(|value| self.config.add(value, 1));
+					state.set_cell(value);
+				},
+				DecrementByte => {
+					let value = state.cell().and_then(|value| self.config.add(value, -1));
+					state.set_cell(value);
+				},
+				InputByte => state.set_cell(None),
+				OutputByte | Debug => {},
+				Loop(ref body) => {
+					let end = pc + command.size() - 1;
+					self.analyze_loop(body, pc, end, state);
+					pc = end;
+				},
+				Procedure(ref body) => {
+					// procedures can be called from anywhere, so nothing is known about the tape in them
+					pc = self.analyze(body, pc + 1, &mut State::unknown());
+				},
+				Call => *state = State::unknown()
+			}
+			pc += 1;
+		}
+
+		pc
+	}
+
+	fn analyze_loop(&mut self, body: &[Command], start: usize, end: usize, state: &mut State) {
+		if state.cell() == Some(0) {
+			return self.report(Lint::DeadLoop, start, end);
+		}
+
+		if stalls(body) {
+			self.report(Lint::InfiniteLoop, start, end);
+		}
+
+		match effects(body) {
+			// the pointer is back where it started after every iteration, so only the cells the
+			// loop changes are no longer known
+			Some((0, written)) => {
+				for offset in written {
+					state.values.insert(state.pos + offset, None);
+				}
+				self.analyze(body, start + 1, &mut state.clone());
+			},
+			_ => {
+				self.analyze(body, start + 1, &mut State::unknown());
+				*state = State::unknown();
+			}
+		}
+
+		// whatever else happened, the loop has ended on a zero
+		state.set_cell(Some(0));
+	}
+}
+
+// whether the loop changes neither the pointer nor its own cell
+fn stalls(body: &[Command]) -> bool {
+	let mut offset = 0;
+	let mut delta = 0;
+
+	for command in body {
+		match *command {
+			IncrementPos => offset += 1,
+			DecrementPos => offset -= 1,
+			IncrementByte if offset == 0 => delta += 1,
+			DecrementByte if offset == 0 => delta -= 1,
+			IncrementByte | DecrementByte | OutputByte | Debug => {},
+			_ => return false
+		}
+	}
+
+	offset == 0 && delta == 0
+}
+
+// how far the commands move the pointer and the offsets of the cells they may change, as long as
+// that doesn't depend on the values of the cells
+fn effects(commands: &[Command]) -> Option<(isize, HashSet<isize>)> {
+	let mut offset = 0;
+	let mut written = HashSet::new();
+
+	for command in commands {
+		match *command {
+			IncrementPos => offset += 1,
+			DecrementPos => offset -= 1,
+			IncrementByte | DecrementByte | InputByte => { written.insert(offset); },
+			OutputByte | Debug | Procedure(_) => {},
+			Loop(ref body) => match effects(body) {
+				Some((0, body_written)) => written.extend(body_written.into_iter().map(|o| offset + o)),
+				_ => return None
+			},
+			Call => return None
+		}
+	}
+
+	Some((offset, written))
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use super::{analyze, report, Diagnostic, Lint};
+	use super::super::{instruction_positions, Dialect, ParseBrainfuck, FIZZBUZZ, HELLOWORLD};
+	use super::super::config::{CellWidth, Overflow, TapeConfig};
+
+	fn lints(source: &[u8]) -> Vec<(Lint, usize, usize)> {
+		let dialect = Dialect { procedures: true, ..Dialect::default() };
+		let program = source.parse_with(&dialect).unwrap();
+
+		analyze(&program, &TapeConfig::classic()).into_iter().map(|d| (d.lint, d.start, d.end)).collect()
+	}
+
+	#[test]
+	fn clean_programs() {
+		assert_eq!(lints(HELLOWORLD), vec![]);
+		assert_eq!(lints(FIZZBUZZ), vec![]);
+		assert_eq!(lints(b"+[>,]<[<]>[.>]"), vec![]);
+	}
+
+	#[test]
+	fn dead_loops() {
+		assert_eq!(lints(b"[a comment.]+[-][never]"), vec![
+			(Lint::DeadLoop, 0, 2),
+			(Lint::DeadLoop, 7, 8)
+		]);
+		// cells away from the pointer are tracked too, as long as the pointer is
+		assert_eq!(lints(b"+>++<[->-<]>[-]>[+]"), vec![(Lint::DeadLoop, 16, 18)]);
+		// loops which may run more than once don't know what their previous iterations did
+		assert_eq!(lints(b"++[>[-]+<-]"), vec![]);
+		assert_eq!(lints(b",[>]<[-]"), vec![]);
+	}
+
+	#[test]
+	fn infinite_loops() {
+		assert_eq!(lints(b"+[]"), vec![(Lint::InfiniteLoop, 1, 2)]);
+		assert_eq!(lints(b",[>+<.]"), vec![(Lint::InfiniteLoop, 1, 6)]);
+		assert_eq!(lints(b",[+-]"), vec![(Lint::InfiniteLoop, 1, 4)]);
+		assert_eq!(lints(b",[>]+[<]"), vec![]);
+	}
+
+	#[test]
+	fn negative_pointers() {
+		assert_eq!(lints(b">><<<+<"), vec![(Lint::NegativePointer, 4, 4)]);
+		assert_eq!(lints(b"+[<+>-]"), vec![(Lint::NegativePointer, 2, 2)]);
+		// after a loop moving the pointer, it could be anywhere
+		assert_eq!(lints(b",[>,]<<"), vec![]);
+
+		let wrapping = TapeConfig { wrap_pointer: true, ..TapeConfig::classic() };
+		assert_eq!(analyze(&b"<".as_ref().parse().unwrap(), &wrapping), vec![]);
+	}
+
+	#[test]
+	fn cell_widths() {
+		let source = b"-+[]".as_ref().parse().unwrap();
+		let checked = TapeConfig { width: CellWidth::U8, overflow: Overflow::Checked, ..TapeConfig::classic() };
+
+		// wrapping brings the cell back to zero, but an overflowing one could hold anything
+		assert_eq!(analyze(&source, &TapeConfig::classic()), vec![Diagnostic { lint: Lint::DeadLoop, start: 2, end: 3 }]);
+		assert_eq!(analyze(&source, &checked), vec![Diagnostic { lint: Lint::InfiniteLoop, start: 2, end: 3 }]);
+	}
+
+	#[test]
+	fn procedures() {
+		// nothing is known inside procedures or after calls
+		assert_eq!(lints(b"(<[])+:[-]"), vec![(Lint::InfiniteLoop, 2, 3)]);
+	}
+
+	#[test]
+	fn reports() {
+		let source = b"[ignored]\n+\n[\n]";
+		let program = source.as_ref().parse().unwrap();
+		let diagnostics = analyze(&program, &TapeConfig::classic());
+
+		assert_eq!(report(&diagnostics, &instruction_positions(source, &Dialect::default())), "\
+1:1-1:9: this loop is never run, as its cell is always zero here
+3:1-4:1: this loop never ends once entered, as it doesn't change its cell
+");
+	}
+}
@@ -0,0 +1,57 @@
+// times the engines against each other: cargo bench --bench engines [-- PROGRAM...]
+
+use std::env;
+use std::io;
+use std::time::{Duration, Instant};
+use brainfuck::{Interpreter, Program, FIZZBUZZ, MANDELBROT};
+use brainfuck::config::{Overflow, TapeConfig};
+use brainfuck::vm::Bytecode;
+
+const PROGRAMS: &[(&str, &[u8])] = &[("fizzbuzz", FIZZBUZZ), ("mandelbrot", MANDELBROT)];
+
+// the fastest of the runs is reported, as it is the one least disturbed by everything else;
+// slow programs are only run until this much time has been spent on them
+const RUNS: usize = 10;
+const TIME_PER_ENGINE: Duration = Duration::from_secs(1);
+
+fn interpreter() -> Interpreter<io::Empty, io::Sink> {
+	Interpreter::new(io::empty(), io::sink(), TapeConfig::classic())
+}
+
+fn time<F: FnMut()>(mut run: F) -> Duration {
+	let mut fastest = Duration::MAX;
+	let mut total = Duration::ZERO;
+
+	for _ in 0..RUNS {
+		let start = Instant::now();
+		run();
+		let elapsed = start.elapsed();
+
+		fastest = fastest.min(elapsed);
+		total += elapsed;
+		if total >= TIME_PER_ENGINE { break; }
+	}
+
+	fastest
+}
+
+fn main() {
+	// cargo passes --bench along to benchmarks
+	let selected: Vec<String> = env::args().skip(1).filter(|arg| !arg.starts_with('-')).collect();
+
+	println!("{:<12} {:>12} {:>12} {:>12}", "program", "tree", "ir", "bytecode");
+
+	for &(name, source) in PROGRAMS {
+		if !selected.is_empty() && !selected.iter().any(|s| s == name) { continue; }
+
+		let program = Program::parse(source).unwrap();
+		let ops = program.ops(Overflow::Wrapping).unwrap();
+		let bytecode = Bytecode::compile(source, Overflow::Wrapping).unwrap();
+
+		let tree = time(|| interpreter().run_unoptimized(&program).unwrap());
+		let ir = time(|| interpreter().run_ir(ops).unwrap());
+		let vm = time(|| interpreter().run_bytecode(&bytecode).unwrap());
+
+		println!("{:<12} {:>11.5}s {:>11.5}s {:>11.5}s", name, tree.as_secs_f64(), ir.as_secs_f64(), vm.as_secs_f64());
+	}
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use super::RuntimeErrorKind;
+use super::RuntimeErrorKind::*;
+
+// reading the clock is much slower than an instruction, so the deadline is only checked this often
+const DEADLINE_INTERVAL: u64 = 1024;
+
+/* execution limits */
+
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+	pub max_steps: Option<u64>,
+	pub deadline: Option<Instant>,
+	// checked between instructions; set it from another thread to stop the program
+	pub cancelled: Option<Arc<AtomicBool>>
+}
+
+impl Budget {
+	pub fn unlimited() -> Budget { Budget::default() }
+
+	pub fn timeout(timeout: Duration) -> Budget {
+		Budget {
+			deadline: Some(Instant::now() + timeout),
+			..Budget::default()
+		}
+	}
+
+	#[inline]
+	pub fn check(&self, steps: u64) -> Result<(), RuntimeErrorKind> {
+		if let Some(max_steps) = self.max_steps {
+			if steps > max_steps { return Err(StepLimitExceeded); }
+		}
+
+		if let Some(ref cancelled) = self.cancelled {
+			if cancelled.load(Ordering::Relaxed) { return Err(Cancelled); }
+		}
+
+		if let Some(deadline) = self.deadline {
+			if steps.is_multiple_of(DEADLINE_INTERVAL) && Instant::now() >= deadline { return Err(TimedOut); }
+		}
+
+		Ok(())
+	}
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use std::sync::Arc;
+	use std::sync::atomic::{AtomicBool, Ordering};
+	use std::thread;
+	use std::time::{Duration, Instant};
+	use super::Budget;
+	use super::super::{Interpreter, Program, RuntimeError};
+	use super::super::config::TapeConfig;
+	use super::super::RuntimeErrorKind::*;
+
+	fn run_limited(source: &[u8], budget: Budget) -> Result<(), RuntimeError> {
+		let mut interpreter = Interpreter::new(io::empty(), io::sink(), TapeConfig::default());
+		interpreter.set_budget(budget);
+		interpreter.run_unoptimized(&Program::parse(source).unwrap())
+	}
+
+	#[test]
+	fn step_limit() {
+		let budget = Budget { max_steps: Some(100), ..Budget::unlimited() };
+
+		assert_eq!(run_limited(b"+++[-]", budget.clone()), Ok(()));
+		assert_eq!(run_limited(b"+[]", budget), Err(RuntimeError { kind: StepLimitExceeded, position: 2, steps: 101 }));
+	}
+
+	#[test]
+	fn step_limit_in_optimized_code() {
+		let mut interpreter = Interpreter::new(io::empty(), io::sink(), TapeConfig::default());
+		interpreter.set_budget(Budget { max_steps: Some(10), ..Budget::unlimited() });
+		let error = interpreter.run(&Program::parse(b"+[>+]").unwrap()).unwrap_err();
+
+		assert_eq!(error.kind, StepLimitExceeded);
+		assert_eq!(error.steps, 11);
+	}
+
+	#[test]
+	fn deadline() {
+		let budget = Budget { deadline: Some(Instant::now()), ..Budget::unlimited() };
+		let error = run_limited(b"+[]", budget).unwrap_err();
+
+		assert_eq!(error.kind, TimedOut);
+		assert_eq!(error.steps, 1024);
+	}
+
+	#[test]
+	fn cancellation() {
+		let cancelled = Arc::new(AtomicBool::new(false));
+		let budget = Budget { cancelled: Some(cancelled.clone()), ..Budget::timeout(Duration::from_secs(60)) };
+
+		let canceller = thread::spawn(move || {
+			thread::sleep(Duration::from_millis(10));
+			cancelled.store(true, Ordering::Relaxed);
+		});
+
+		assert_eq!(run_limited(b"+[>+<]", budget).unwrap_err().kind, Cancelled);
+		canceller.join().unwrap();
+	}
+}
@@ -0,0 +1,335 @@
+use std::time::Duration;
+use brainfuck::Dialect;
+use brainfuck::budget::Budget;
+use brainfuck::languages::{self, Language};
+use brainfuck::config::{CellWidth, EofPolicy, Overflow, TapeConfig};
+use brainfuck::trace;
+use brainfuck::transpile::Target;
+
+pub const USAGE: &str = "usage: brainfuck [options] (FILE | -e CODE)
+
+options:
+    -e, --eval CODE        run CODE instead of a file
+    -i, --input FILE       read the program's input from FILE instead of stdin
+        --cells N          the number of cells on the tape (default: 30000)
+        --cell-width BITS  8, 16, 32 or 64 (default: 8)
+        --overflow MODE    wrap, saturate or error (default: wrap)
+        --eof MODE         unchanged, zero, minus-one or error (default: unchanged)
+        --growable         extend the tape whenever the pointer moves past its end
+        --wrap-pointer     continue from the other edge of the tape when leaving it
+        --extensions LIST  enable language extensions, separated by commas: pbrain (procedures),
+                           dump ('#' prints the tape) and input (the input follows a '!')
+        --max-steps N      abort after executing N instructions
+        --timeout SECONDS  abort after running for the given time
+        --engine ENGINE    tree (walk the program as written), ir (walk the optimized program)
+                           or bytecode (compile it for the bytecode VM; the default)
+        --no-optimize      run the program without optimizing it first, like --engine tree
+        --checkpoint FILE  save the state of the program to FILE if it's stopped by --max-steps or
+                           --timeout, so that it can be resumed (bytecode engine only)
+        --resume FILE      continue the program from the state saved in FILE, given the same input
+                           and --cells at least as large as the saved tape
+        --emit LANGUAGE    print the program transpiled to c or rust instead of running it
+        --language NAME    read the program in ook, blub or pikalang instead of brainfuck
+        --convert NAME     print the program in brainfuck, ook, blub or pikalang instead of running it
+        --minify           print the program without comments or redundant instructions
+        --format           print the program indented by loop nesting, keeping comments
+        --generate         print a program printing the text of FILE or CODE instead of running it
+        --lint             print dead loops, infinite loops and moves left of the first cell
+                           instead of running the program; fails if there are any
+        --stats            print the number of steps and the running time to stderr
+        --profile          print how often the hottest loops ran to stderr
+        --debug            step through the program interactively; '#' pauses it
+        --trace FORMAT     record every instruction executed with the pointer and its cell, as
+                           binary, json (a line per instruction) or strip (the tape around the pointer)
+        --trace-file FILE  write the trace to FILE instead of stderr
+    -h, --help             print this message";
+
+/* options */
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Engine {
+	Tree,
+	Ir,
+	Bytecode
+}
+
+// brainfuck, or one of the languages spelling its instructions differently
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Syntax {
+	Brainfuck,
+	Substitution(&'static Language)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Source {
+	File(String),
+	Inline(String)
+}
+
+#[derive(Debug)]
+pub struct Options {
+	pub source: Source,
+	pub input: Option<String>,
+	pub config: TapeConfig,
+	pub dialect: Dialect,
+	pub budget: Budget,
+	// kept apart from the budget, as the time should only start running along with the program
+	pub timeout: Option<Duration>,
+	pub engine: Engine,
+	pub checkpoint: Option<String>,
+	pub resume: Option<String>,
+	pub emit: Option<Target>,
+	pub language: Syntax,
+	pub convert: Option<Syntax>,
+	pub minify: bool,
+	pub format: bool,
+	pub generate: bool,
+	pub lint: bool,
+	pub stats: bool,
+	pub profile: bool,
+	pub debug: bool,
+	pub trace: Option<trace::Format>,
+	pub trace_file: Option<String>
+}
+
+// returns Ok(None) if the usage was requested
+pub fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Options>, String> {
+	let mut source = None;
+	let mut input = None;
+	let mut config = TapeConfig::classic();
+	let mut dialect = Dialect::default();
+	let mut budget = Budget::unlimited();
+	let mut timeout = None;
+	let mut engine = Engine::Bytecode;
+	let mut checkpoint = None;
+	let mut resume = None;
+	let mut emit = None;
+	let mut language = Syntax::Brainfuck;
+	let mut convert = None;
+	let mut minify = false;
+	let mut format = false;
+	let mut generate = false;
+	let mut lint = false;
+	let mut stats = false;
+	let mut profile = false;
+	let mut debug = false;
+	let mut trace = None;
+	let mut trace_file = None;
+
+	while let Some(arg) = args.next() {
+		match &*arg {
+			"-h" | "--help" => return Ok(None),
+			"-e" | "--eval" => source = Some(Source::Inline(value(&arg, args.next())?)),
+			"-i" | "--input" => input = Some(value(&arg, args.next())?),
+			"--cells" => config.cells = number(&arg, args.next())?,
+			"--cell-width" => config.width = match &*value(&arg, args.next())? {
+				"8" => CellWidth::U8,
+				"16" => CellWidth::U16,
+				"32" => CellWidth::U32,
+				"64" => CellWidth::I64,
+				other => return Err(format!("invalid cell width: {}", other))
+			},
+			"--overflow" => config.overflow = match &*value(&arg, args.next())? {
+				"wrap" => Overflow::Wrapping,
+				"saturate" => Overflow::Saturating,
+				"error" => Overflow::Checked,
+				other => return Err(format!("invalid overflow mode: {}", other))
+			},
+			"--eof" => config.eof = match &*value(&arg, args.next())? {
+				"unchanged" => EofPolicy::Unchanged,
+				"zero" => EofPolicy::Zero,
+				"minus-one" => EofPolicy::MinusOne,
+				"error" => EofPolicy::Error,
+				other => return Err(format!("invalid EOF mode: {}", other))
+			},
+			"--growable" => config.growable = true,
+			"--wrap-pointer" => config.wrap_pointer = true,
+			"--extensions" => for extension in value(&arg, args.next())?.split(',') {
+				match extension {
+					"pbrain" => dialect.procedures = true,
+					"dump" => dialect.debug = true,
+					"input" => dialect.input_separator = true,
+					other => return Err(format!("unknown extension: {}", other))
+				}
+			},
+			"--max-steps" => budget.max_steps = Some(number(&arg, args.next())?),
+			"--timeout" => {
+				let seconds: f64 = number(&arg, args.next())?;
+				timeout = Some(Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid timeout: {}", seconds))?);
+			},
+			"--engine" => engine = match &*value(&arg, args.next())? {
+				"tree" => Engine::Tree,
+				"ir" => Engine::Ir,
+				"bytecode" => Engine::Bytecode,
+				other => return Err(format!("invalid engine: {}", other))
+			},
+			"--no-optimize" => engine = Engine::Tree,
+			"--checkpoint" => checkpoint = Some(value(&arg, args.next())?),
+			"--resume" => resume = Some(value(&arg, args.next())?),
+			"--emit" => emit = match &*value(&arg, args.next())? {
+				"c" => Some(Target::C),
+				"rust" => Some(Target::Rust),
+				other => return Err(format!("invalid language: {}", other))
+			},
+			"--language" => language = syntax(&value(&arg, args.next())?)?,
+			"--convert" => convert = Some(syntax(&value(&arg, args.next())?)?),
+			"--minify" => minify = true,
+			"--format" => format = true,
+			"--generate" => generate = true,
+			"--lint" => lint = true,
+			"--stats" => stats = true,
+			"--profile" => profile = true,
+			"--debug" => {
+				debug = true;
+				dialect.debug = true;
+			},
+			"--trace" => trace = match &*value(&arg, args.next())? {
+				"binary" => Some(trace::Format::Binary),
+				"json" => Some(trace::Format::JsonLines),
+				"strip" => Some(trace::Format::Strip),
+				other => return Err(format!("invalid trace format: {}", other))
+			},
+			"--trace-file" => trace_file = Some(value(&arg, args.next())?),
+			_ if arg.starts_with('-') && arg.len() > 1 => return Err(format!("unknown option: {}", arg)),
+			_ => match source {
+				None => source = Some(Source::File(arg)),
+				Some(_) => return Err(format!("unexpected argument: {}", arg))
+			}
+		}
+	}
+
+	if config.cells == 0 {
+		return Err("the tape needs at least one cell".to_owned());
+	}
+
+	match source {
+		Some(source) => Ok(Some(Options {
+			source: source,
+			input: input,
+			config: config,
+			dialect: dialect,
+			budget: budget,
+			timeout: timeout,
+			engine: engine,
+			checkpoint: checkpoint,
+			resume: resume,
+			emit: emit,
+			language: language,
+			convert: convert,
+			minify: minify,
+			format: format,
+			generate: generate,
+			lint: lint,
+			stats: stats,
+			profile: profile,
+			debug: debug,
+			trace: trace,
+			trace_file: trace_file
+		})),
+		None => Err("no program given".to_owned())
+	}
+}
+
+fn syntax(name: &str) -> Result<Syntax, String> {
+	match languages::by_name(name) {
+		Some(language) => Ok(Syntax::Substitution(language)),
+		None if name == "brainfuck" => Ok(Syntax::Brainfuck),
+		None => Err(format!("unknown language: {}", name))
+	}
+}
+
+fn value(option: &str, value: Option<String>) -> Result<String, String> {
+	value.ok_or_else(|| format!("missing value for {}", option))
+}
+
+fn number<T: ::std::str::FromStr>(option: &str, arg: Option<String>) -> Result<T, String> {
+	let arg = value(option, arg)?;
+	arg.parse().map_err(|_| format!("invalid value for {}: {}", option, arg))
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use super::{parse_args, Engine, Options, Source, Syntax};
+	use brainfuck::config::{CellWidth, EofPolicy, Overflow};
+	use brainfuck::languages;
+	use brainfuck::trace;
+	use brainfuck::transpile::Target;
+
+	fn parse(args: &[&str]) -> Result<Option<Options>, String> {
+		parse_args(args.iter().map(|arg| arg.to_string()))
+	}
+
+	#[test]
+	fn parsing_sources() {
+		assert_eq!(parse(&["hello.b"]).unwrap().unwrap().source, Source::File("hello.b".to_owned()));
+		assert_eq!(parse(&["-e", "+[-]"]).unwrap().unwrap().source, Source::Inline("+[-]".to_owned()));
+		assert_eq!(parse(&[]).unwrap_err(), "no program given");
+		assert_eq!(parse(&["a.b", "b.b"]).unwrap_err(), "unexpected argument: b.b");
+		assert!(parse(&["a.b", "--help"]).unwrap().is_none());
+	}
+
+	#[test]
+	fn parsing_dialects() {
+		let options = parse(&["--cell-width", "16", "--overflow", "saturate", "--eof", "minus-one",
+			"--cells", "100", "--growable", "--wrap-pointer", "a.b"]).unwrap().unwrap();
+
+		assert_eq!(options.config.width, CellWidth::U16);
+		assert_eq!(options.config.overflow, Overflow::Saturating);
+		assert_eq!(options.config.eof, EofPolicy::MinusOne);
+		assert_eq!(options.config.cells, 100);
+		assert!(options.config.growable && options.config.wrap_pointer);
+
+		let dialect = parse(&["--extensions", "pbrain,input", "a.b"]).unwrap().unwrap().dialect;
+		assert!(dialect.procedures && dialect.input_separator && !dialect.debug);
+	}
+
+	#[test]
+	fn parsing_limits_and_flags() {
+		let options = parse(&["--max-steps", "1000", "--timeout", "1.5", "--stats", "--no-optimize",
+			"-i", "input.txt", "a.b"]).unwrap().unwrap();
+
+		assert_eq!(options.budget.max_steps, Some(1000));
+		assert_eq!(options.timeout, Some(Duration::from_millis(1500)));
+		assert!(options.stats);
+		assert_eq!(options.engine, Engine::Tree);
+		assert_eq!(options.input, Some("input.txt".to_owned()));
+		assert_eq!(parse(&["--emit", "rust", "a.b"]).unwrap().unwrap().emit, Some(Target::Rust));
+		assert!(parse(&["--debug", "a.b"]).unwrap().unwrap().dialect.debug);
+		assert!(parse(&["--profile", "a.b"]).unwrap().unwrap().profile);
+		assert!(parse(&["--format", "a.b"]).unwrap().unwrap().format);
+		assert!(parse(&["--lint", "a.b"]).unwrap().unwrap().lint);
+		assert!(parse(&["--generate", "-e", "text"]).unwrap().unwrap().generate);
+		assert_eq!(parse(&["a.b"]).unwrap().unwrap().engine, Engine::Bytecode);
+		assert_eq!(parse(&["--engine", "ir", "a.b"]).unwrap().unwrap().engine, Engine::Ir);
+		assert_eq!(parse(&["--resume", "state", "a.b"]).unwrap().unwrap().resume, Some("state".to_owned()));
+
+		let options = parse(&["--language", "ook", "--convert", "brainfuck", "a.ook"]).unwrap().unwrap();
+		assert_eq!(options.language, Syntax::Substitution(&languages::OOK));
+		assert_eq!(options.convert, Some(Syntax::Brainfuck));
+		assert_eq!(parse(&["a.b"]).unwrap().unwrap().language, Syntax::Brainfuck);
+
+		let options = parse(&["--trace", "json", "--trace-file", "trace.jsonl", "a.b"]).unwrap().unwrap();
+		assert_eq!(options.trace, Some(trace::Format::JsonLines));
+		assert_eq!(options.trace_file, Some("trace.jsonl".to_owned()));
+	}
+
+	#[test]
+	fn invalid_arguments() {
+		assert_eq!(parse(&["--cell-width", "12", "a.b"]).unwrap_err(), "invalid cell width: 12");
+		assert_eq!(parse(&["--cells", "lots", "a.b"]).unwrap_err(), "invalid value for --cells: lots");
+		assert_eq!(parse(&["--cells", "0", "a.b"]).unwrap_err(), "the tape needs at least one cell");
+		assert_eq!(parse(&["a.b", "--max-steps"]).unwrap_err(), "missing value for --max-steps");
+		assert_eq!(parse(&["--verbose", "a.b"]).unwrap_err(), "unknown option: --verbose");
+		assert_eq!(parse(&["--emit", "cobol", "a.b"]).unwrap_err(), "invalid language: cobol");
+		assert_eq!(parse(&["--engine", "jit", "a.b"]).unwrap_err(), "invalid engine: jit");
+		assert_eq!(parse(&["--trace", "xml", "a.b"]).unwrap_err(), "invalid trace format: xml");
+		assert_eq!(parse(&["--timeout", "-1", "a.b"]).unwrap_err(), "invalid timeout: -1");
+		assert_eq!(parse(&["--timeout", "1e30", "a.b"]).unwrap_err(), "invalid timeout: 1000000000000000000000000000000");
+		assert_eq!(parse(&["--timeout", "inf", "a.b"]).unwrap_err(), "invalid timeout: inf");
+		assert_eq!(parse(&["--convert", "cow", "a.b"]).unwrap_err(), "unknown language: cow");
+		assert_eq!(parse(&["--extensions", "pbrain,goto", "a.b"]).unwrap_err(), "unknown extension: goto");
+	}
+}
@@ -0,0 +1,144 @@
+/* tape dialects */
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EofPolicy {
+	Unchanged,
+	Zero,
+	MinusOne,
+	Error
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CellWidth {
+	U8,
+	U16,
+	U32,
+	I64
+}
+
+impl CellWidth {
+	#[inline]
+	pub fn min(self) -> i64 {
+		match self {
+			CellWidth::I64 => i64::MIN,
+			_ => 0
+		}
+	}
+
+	#[inline]
+	pub fn max(self) -> i64 {
+		match self {
+			CellWidth::U8 => u8::MAX as i64,
+			CellWidth::U16 => u16::MAX as i64,
+			CellWidth::U32 => u32::MAX as i64,
+			CellWidth::I64 => i64::MAX
+		}
+	}
+
+	// brings any value into the cell's range the way two's complement arithmetic would
+	#[inline]
+	pub fn wrap(self, value: i128) -> i64 {
+		match self {
+			CellWidth::I64 => value as i64,
+			_ => value.rem_euclid(self.max() as i128 + 1) as i64
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Overflow {
+	Wrapping,
+	Saturating,
+	Checked
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TapeConfig {
+	pub cells: usize,
+	pub width: CellWidth,
+	pub overflow: Overflow,
+	// growable tapes are extended whenever the pointer moves past their right end
+	pub growable: bool,
+	// moving past either edge of the tape continues from the other one
+	pub wrap_pointer: bool,
+	pub eof: EofPolicy
+}
+
+impl Default for TapeConfig {
+	fn default() -> TapeConfig {
+		TapeConfig {
+			cells: 30000,
+			width: CellWidth::I64,
+			overflow: Overflow::Wrapping,
+			growable: false,
+			wrap_pointer: false,
+			eof: EofPolicy::Unchanged
+		}
+	}
+}
+
+impl TapeConfig {
+	// the common 8-bit wrapping dialect most programs in the wild are written for
+	pub fn classic() -> TapeConfig {
+		TapeConfig {
+			width: CellWidth::U8,
+			..TapeConfig::default()
+		}
+	}
+
+	// returns None if the result doesn't fit in a cell and the arithmetic is checked
+	#[inline]
+	pub fn add(&self, value: i64, delta: i128) -> Option<i64> {
+		let sum = value as i128 + delta;
+		let (min, max) = (self.width.min(), self.width.max());
+
+		if sum >= min as i128 && sum <= max as i128 {
+			return Some(sum as i64);
+		}
+
+		match self.overflow {
+			Overflow::Wrapping => Some(self.width.wrap(sum)),
+			Overflow::Saturating => Some(if sum < min as i128 { min } else { max }),
+			Overflow::Checked => None
+		}
+	}
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use super::{CellWidth, Overflow, TapeConfig};
+
+	fn config(width: CellWidth, overflow: Overflow) -> TapeConfig {
+		TapeConfig {
+			width: width,
+			overflow: overflow,
+			..TapeConfig::default()
+		}
+	}
+
+	#[test]
+	fn wrapping_arithmetic() {
+		assert_eq!(config(CellWidth::U8, Overflow::Wrapping).add(255, 1), Some(0));
+		assert_eq!(config(CellWidth::U8, Overflow::Wrapping).add(0, -1), Some(255));
+		assert_eq!(config(CellWidth::U8, Overflow::Wrapping).add(10, 10 * 256 + 3), Some(13));
+		assert_eq!(config(CellWidth::U16, Overflow::Wrapping).add(0, -1), Some(65535));
+		assert_eq!(config(CellWidth::U32, Overflow::Wrapping).add(4294967295, 2), Some(1));
+		assert_eq!(config(CellWidth::I64, Overflow::Wrapping).add(i64::MAX, 1), Some(i64::MIN));
+	}
+
+	#[test]
+	fn saturating_arithmetic() {
+		assert_eq!(config(CellWidth::U8, Overflow::Saturating).add(250, 10), Some(255));
+		assert_eq!(config(CellWidth::U8, Overflow::Saturating).add(5, -10), Some(0));
+		assert_eq!(config(CellWidth::I64, Overflow::Saturating).add(i64::MIN, -1), Some(i64::MIN));
+	}
+
+	#[test]
+	fn checked_arithmetic() {
+		assert_eq!(config(CellWidth::U8, Overflow::Checked).add(254, 1), Some(255));
+		assert_eq!(config(CellWidth::U8, Overflow::Checked).add(255, 1), None);
+		assert_eq!(config(CellWidth::U16, Overflow::Checked).add(0, -1), None);
+	}
+}
@@ -0,0 +1,373 @@
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use super::{instructions, Command, Observer, RuntimeErrorKind, Tape};
+
+// how many cells on either side of the pointer are shown by default
+const RADIUS: usize = 8;
+
+const HELP: &str = "commands:
+    s, step            execute a single instruction
+    n, next            like step, but runs a loop that's about to start to its end
+    c, continue        run until a breakpoint or a '#'
+    u, until N         run until instruction N
+    b, break N         set a breakpoint at instruction N
+    d, delete N        remove the breakpoint at instruction N
+    t, tape [RADIUS]   show the cells around the pointer (and change the default radius)
+    w, where           show the current instruction and loop nesting
+    q, quit            stop the program
+    h, help            print this message
+an empty line repeats the previous command";
+
+// how far to run before pausing again
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Mode {
+	Step,
+	Continue,
+	RunTo(usize),
+	// pauses at the loop's ']' once the loop is about to end, for loops nothing is run after; a loop
+	// that is skipped or returned from pauses at whatever runs next instead
+	LeaveLoop(usize, usize)
+}
+
+/* debugging */
+
+pub struct Debugger<R, W> {
+	source: Vec<u8>,
+	// the position of the matching bracket for every bracket
+	matches: Vec<Option<usize>>,
+	breakpoints: BTreeSet<usize>,
+	mode: Mode,
+	radius: usize,
+	last_command: String,
+	commands: R,
+	output: W
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+	// reads debugger commands from `commands`; starts paused before the first instruction
+	pub fn new(program: &[Command], commands: R, output: W) -> Debugger<R, W> {
+		let source = instructions(program);
+		let mut matches = vec![None; source.len()];
+		let mut open = Vec::new();
+
+		for (pc, &c) in source.iter().enumerate() {
+			match c {
+				b'[' | b'(' => open.push(pc),
+				b']' | b')' => {
+					let start = open.pop().unwrap();
+					matches[start] = Some(pc);
+					matches[pc] = Some(start);
+				},
+				_ => {}
+			}
+		}
+
+		Debugger {
+			source: source,
+			matches: matches,
+			breakpoints: BTreeSet::new(),
+			mode: Mode::Step,
+			radius: RADIUS,
+			last_command: String::new(),
+			commands: commands,
+			output: output
+		}
+	}
+
+	pub fn set_breakpoint(&mut self, pc: usize) { self.breakpoints.insert(pc); }
+
+	// the positions of the opening brackets of the loops the instruction is in
+	fn loops(&self, pc: usize) -> Vec<usize> {
+		let mut open = Vec::new();
+
+		for (i, &c) in self.source[..pc + 1].iter().enumerate() {
+			match c {
+				b'[' | b'(' if i < pc => open.push(i),
+				b']' | b')' => { open.pop(); },
+				_ => {}
+			}
+		}
+
+		open.into_iter().filter(|&i| self.source[i] == b'[').collect()
+	}
+
+	fn show_position(&mut self, tape: &Tape, pc: usize) {
+		let start = pc.saturating_sub(30);
+		let end = (pc + 31).min(self.source.len());
+		let loops = self.loops(pc).iter().map(|start| start.to_string()).collect::<Vec<_>>();
+
+		let _ = writeln!(self.output, "instruction {} '{}' after {} steps", pc, self.source[pc] as char, tape.steps);
+		if !loops.is_empty() {
+			let _ = writeln!(self.output, "inside the loops starting at {}", loops.join(", "));
+		}
+		let _ = writeln!(self.output, "    {}", String::from_utf8_lossy(&self.source[start..end]));
+		let _ = writeln!(self.output, "    {}^", " ".repeat(pc - start));
+	}
+
+	fn show_tape(&mut self, tape: &Tape) {
+		let _ = writeln!(self.output, "{}", tape_window(tape, self.radius));
+	}
+
+	fn prompt(&mut self, tape: &Tape, pc: usize) -> Result<(), RuntimeErrorKind> {
+		self.show_position(tape, pc);
+		self.show_tape(tape);
+
+		loop {
+			let _ = write!(self.output, "(bf) ");
+			let _ = self.output.flush();
+
+			let mut line = String::new();
+			match self.commands.read_line(&mut line) {
+				Ok(0) | Err(_) => {
+					// nobody is around to give commands anymore, so let the program finish
+					self.mode = Mode::Continue;
+					self.breakpoints.clear();
+					return Ok(());
+				},
+				Ok(_) => {}
+			}
+
+			let line = match line.trim() {
+				"" => self.last_command.clone(),
+				line => line.to_owned()
+			};
+			self.last_command = line.clone();
+
+			let mut words = line.split_whitespace();
+			let command = words.next().unwrap_or("");
+			let argument = words.next().map(|arg| arg.parse::<usize>());
+
+			match (command, argument) {
+				("s", None) | ("step", None) => {
+					self.mode = Mode::Step;
+					return Ok(());
+				},
+				("n", None) | ("next", None) => {
+					self.mode = match (self.source[pc], self.matches[pc]) {
+						// the end of the program or of a procedure is never reached like an instruction
+						(b'[', Some(end)) if end + 1 == self.source.len() || self.source[end + 1] == b')' =>
+							Mode::LeaveLoop(pc, end),
+						(b'[', Some(end)) => Mode::RunTo(end + 1),
+						_ => Mode::Step
+					};
+					return Ok(());
+				},
+				("c", None) | ("continue", None) => {
+					self.mode = Mode::Continue;
+					return Ok(());
+				},
+				("u", Some(Ok(target))) | ("until", Some(Ok(target))) if target < self.source.len() => {
+					self.mode = Mode::RunTo(target);
+					return Ok(());
+				},
+				("b", Some(Ok(target))) | ("break", Some(Ok(target))) if target < self.source.len() => {
+					self.breakpoints.insert(target);
+					let _ = writeln!(self.output, "breakpoint at instruction {}", target);
+				},
+				("d", Some(Ok(target))) | ("delete", Some(Ok(target))) => {
+					if !self.breakpoints.remove(&target) {
+						let _ = writeln!(self.output, "no breakpoint at instruction {}", target);
+					}
+				},
+				("u", Some(Ok(_))) | ("until", Some(Ok(_))) | ("b", Some(Ok(_))) | ("break", Some(Ok(_))) => {
+					let _ = writeln!(self.output, "there are only {} instructions", self.source.len());
+				},
+				("t", None) | ("tape", None) => self.show_tape(tape),
+				("t", Some(Ok(radius))) | ("tape", Some(Ok(radius))) => {
+					self.radius = radius;
+					self.show_tape(tape);
+				},
+				("w", None) | ("where", None) => self.show_position(tape, pc),
+				("q", None) | ("quit", None) => return Err(RuntimeErrorKind::Cancelled),
+				("h", None) | ("help", None) => { let _ = writeln!(self.output, "{}", HELP); },
+				_ => { let _ = writeln!(self.output, "invalid command; try 'help'"); }
+			}
+		}
+	}
+}
+
+impl<R: BufRead, W: Write> Observer for Debugger<R, W> {
+	fn observe(&mut self, tape: &Tape, pc: usize) -> Result<(), RuntimeErrorKind> {
+		let pause = match self.mode {
+			Mode::Step => true,
+			Mode::RunTo(target) => pc == target,
+			Mode::LeaveLoop(start, end) => pc < start || pc > end || (pc == end && tape.cell() == 0),
+			Mode::Continue => false
+		};
+
+		if pause || self.breakpoints.contains(&pc) || self.source[pc] == b'#' {
+			self.prompt(tape, pc)
+		} else {
+			Ok(())
+		}
+	}
+}
+
+/* dumping */
+
+// prints the cells around the pointer whenever a '#' is reached
+pub struct TapeDump<W> {
+	source: Vec<u8>,
+	output: W
+}
+
+impl<W: Write> TapeDump<W> {
+	pub fn new(program: &[Command], output: W) -> TapeDump<W> {
+		TapeDump {
+			source: instructions(program),
+			output: output
+		}
+	}
+}
+
+impl<W: Write> Observer for TapeDump<W> {
+	fn observe(&mut self, tape: &Tape, pc: usize) -> Result<(), RuntimeErrorKind> {
+		if self.source[pc] == b'#' {
+			writeln!(self.output, "#{}: {}", pc, tape_window(tape, RADIUS))
+				.map_err(|e| RuntimeErrorKind::Output(e.kind()))?;
+		}
+		Ok(())
+	}
+}
+
+// the cells within the given distance from the pointer, the current one in brackets
+fn tape_window(tape: &Tape, radius: usize) -> String {
+	let start = tape.pos.saturating_sub(radius);
+	let end = (tape.pos + radius + 1).min(tape.array.len());
+	let cells: Vec<String> = (start..end).map(|i| {
+		if i == tape.pos { format!("[{}]", tape.array[i]) } else { tape.array[i].to_string() }
+	}).collect();
+
+	format!("cells {}-{}: {}", start, end - 1, cells.join(" "))
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use super::{tape_window, Debugger, TapeDump};
+	use super::super::{Dialect, Interpreter, Program, RuntimeError, Tape};
+	use super::super::config::TapeConfig;
+	use super::super::RuntimeErrorKind::Cancelled;
+
+	fn debug(source: &[u8], commands: &str) -> (Tape, Result<(), RuntimeError>, String) {
+		let program = Program::parse_with(source, &Dialect { debug: true, ..Dialect::default() }).unwrap();
+		let mut interpreter = Interpreter::new(io::empty(), io::sink(), TapeConfig::default());
+		let mut output = Vec::new();
+		let result = {
+			let mut debugger = Debugger::new(program.commands(), commands.as_bytes(), &mut output);
+			interpreter.run_observed(&program, &mut debugger)
+		};
+
+		(interpreter.tape, result, String::from_utf8(output).unwrap())
+	}
+
+	fn pauses(output: &str) -> Vec<usize> {
+		// the prompt isn't followed by a newline, so it precedes everything printed after it
+		output.lines()
+			.map(|line| line.trim_start_matches("(bf) "))
+			.filter(|line| line.starts_with("instruction "))
+			.map(|line| line.split(' ').nth(1).unwrap().parse().unwrap())
+			.collect()
+	}
+
+	#[test]
+	fn stepping() {
+		let (tape, result, output) = debug(b"++>+", "s\n\nq\n");
+
+		assert_eq!(pauses(&output), vec![0, 1, 2]);
+		assert_eq!(result.unwrap_err().kind, Cancelled);
+		assert_eq!(tape.array[..2], [2, 0]);
+	}
+
+	#[test]
+	fn breakpoints() {
+		let (tape, result, output) = debug(b"+++[->+<]>>+", "b 6\nc\nc\nd 6\nc\n");
+
+		// the breakpoint is hit on every iteration until it's deleted
+		assert_eq!(pauses(&output), vec![0, 6, 6]);
+		assert_eq!(result, Ok(()));
+		assert_eq!(tape.array[..3], [0, 3, 1]);
+	}
+
+	#[test]
+	fn stepping_over_loops() {
+		let (_, _, output) = debug(b"++[->+<]>.", "u 2\nn\nn\nq\n");
+
+		assert_eq!(pauses(&output), vec![0, 2, 8, 9]);
+
+		// a loop ending the program pauses at its last ']' instead
+		let (tape, result, output) = debug(b"+++[>+<-]", "u 3\nn\nt 1\nc\n");
+		assert_eq!(pauses(&output), vec![0, 3, 8]);
+		assert!(output.contains("cells 0-1: [0] 3\n"));
+		assert_eq!(result, Ok(()));
+		assert_eq!(tape.array[..2], [0, 3]);
+	}
+
+	#[test]
+	fn loop_nesting() {
+		let (_, _, output) = debug(b"+[>+[-]<-]", "u 5\nq\n");
+
+		assert!(output.contains("instruction 5 '-' after 6 steps\ninside the loops starting at 1, 4\n"));
+	}
+
+	#[test]
+	fn debug_characters() {
+		let (_, result, output) = debug(b"+#+>#", "c\nc\nt\nc\n");
+
+		assert_eq!(pauses(&output), vec![0, 1, 4]);
+		assert!(output.contains("cells 0-9: 2 [0] 0 0 0 0 0 0 0 0\n"));
+		assert_eq!(result, Ok(()));
+	}
+
+	#[test]
+	fn running_out_of_commands() {
+		let (tape, result, _) = debug(b"+++[-]#", "b 4\n");
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(tape.array[0], 0);
+	}
+
+	#[test]
+	fn tape_windows() {
+		let mut interpreter = Interpreter::new(io::empty(), io::sink(), TapeConfig::default());
+		interpreter.run(&Program::parse(b"+>++>>>>+++").unwrap()).unwrap();
+		let tape = interpreter.tape();
+
+		assert_eq!(tape_window(tape, 2), "cells 3-7: 0 0 [3] 0 0");
+		assert_eq!(tape_window(tape, 6), "cells 0-11: 1 2 0 0 0 [3] 0 0 0 0 0 0");
+	}
+
+	#[test]
+	fn procedures() {
+		let program = Program::parse_with(b"+(>+<-[:]):", &Dialect { procedures: true, ..Dialect::default() }).unwrap();
+		let mut output = Vec::new();
+		let mut debugger = Debugger::new(program.commands(), "u 5\nw\nc\n".as_bytes(), &mut output);
+		Interpreter::new(io::empty(), io::sink(), TapeConfig::default()).run_observed(&program, &mut debugger).unwrap();
+
+		// the procedure is skipped when it's defined and only entered by the call
+		assert!(String::from_utf8(output).unwrap().contains("instruction 5 '-' after 7 steps\n"));
+	}
+
+	#[test]
+	fn skipping_loops_in_procedures() {
+		// the loop ending the procedure is skipped, so there's no ']' to pause at
+		let program = Program::parse_with(b"(>[-])::+", &Dialect { procedures: true, ..Dialect::default() }).unwrap();
+		let mut output = Vec::new();
+		let mut debugger = Debugger::new(program.commands(), "u 2\nn\nc\n".as_bytes(), &mut output);
+		Interpreter::new(io::empty(), io::sink(), TapeConfig::default()).run_observed(&program, &mut debugger).unwrap();
+
+		assert_eq!(pauses(&String::from_utf8(output).unwrap()), vec![0, 2, 7]);
+	}
+
+	#[test]
+	fn dumping_the_tape() {
+		let program = Program::parse_with(b"+>++#<#", &Dialect { debug: true, ..Dialect::default() }).unwrap();
+		let mut dump = TapeDump::new(program.commands(), Vec::new());
+		Interpreter::new(io::empty(), io::sink(), TapeConfig::default()).run_observed(&program, &mut dump).unwrap();
+
+		assert_eq!(String::from_utf8(dump.output).unwrap(),
+			"#4: cells 0-9: 1 [2] 0 0 0 0 0 0 0 0\n#6: cells 0-8: [1] 2 0 0 0 0 0 0 0\n");
+	}
+}
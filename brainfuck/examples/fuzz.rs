@@ -0,0 +1,68 @@
+// compares the execution backends on random programs for as long as asked to:
+// cargo run --release --features fuzz --example fuzz -- [--seed N] [--programs N] [--transpile]
+
+use std::env;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+use brainfuck::config::{CellWidth, EofPolicy, Overflow, TapeConfig};
+use brainfuck::fuzz::{self, Backend, Rng};
+use brainfuck::transpile::Target;
+
+const MAX_STEPS: u64 = 100_000;
+
+fn usage() -> ! {
+	eprintln!("usage: fuzz [--seed N] [--programs N] [--transpile]");
+	process::exit(2)
+}
+
+fn number(arg: Option<String>) -> u64 {
+	arg.and_then(|arg| arg.parse().ok()).unwrap_or_else(|| usage())
+}
+
+fn main() {
+	let mut seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+	let mut programs = 100_000;
+	let mut backends = fuzz::IN_PROCESS.to_vec();
+
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		match &*arg {
+			"--seed" => seed = number(args.next()),
+			"--programs" => programs = number(args.next()),
+			"--transpile" => backends.extend(&[Backend::Transpiled(Target::C), Backend::Transpiled(Target::Rust)]),
+			_ => usage()
+		}
+	}
+
+	let classic = TapeConfig { eof: EofPolicy::Zero, ..TapeConfig::classic() };
+	let configs = [
+		classic,
+		TapeConfig { width: CellWidth::I64, ..classic },
+		TapeConfig { cells: 16, wrap_pointer: true, ..classic },
+		TapeConfig { cells: 16, growable: true, width: CellWidth::U16, ..classic },
+		TapeConfig { overflow: Overflow::Saturating, ..classic },
+		TapeConfig { cells: 16, overflow: Overflow::Checked, eof: EofPolicy::MinusOne, ..classic }
+	];
+
+	println!("seed {}", seed);
+	let mut rng = Rng::new(seed);
+	let mut checked = 0;
+
+	for i in 0..programs {
+		let length = 1 + rng.below(100) as usize;
+		let program = fuzz::random_program(&mut rng, length, 4);
+		let input: Vec<u8> = (0..rng.below(16)).map(|_| rng.next_u64() as u8).collect();
+		let config = configs[i as usize % configs.len()];
+
+		match fuzz::check(&program, &input, &config, MAX_STEPS, &backends) {
+			Ok(true) => checked += 1,
+			Ok(false) => {},
+			Err(mismatch) => {
+				eprintln!("{}\nconfig: {:?}", mismatch, config);
+				process::exit(1)
+			}
+		}
+	}
+
+	println!("{} programs, {} of them compared", programs, checked);
+}
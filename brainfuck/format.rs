@@ -0,0 +1,259 @@
+use std::fmt;
+use super::{instructions, Command, Dialect, ParseBrainfuck, ParseError, Program};
+use super::config::{Overflow, TapeConfig};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Style {
+	// repeated once per level of nesting
+	pub indent: String,
+	// the most instructions put on a single line
+	pub width: usize,
+	// whether formatting source keeps its comments
+	pub comments: bool
+}
+
+impl Default for Style {
+	fn default() -> Style {
+		Style {
+			indent: "\t".to_owned(),
+			width: 64,
+			comments: true
+		}
+	}
+}
+
+// the pieces of source a formatter lays out
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+	Instruction(u8),
+	Comment(&'a [u8])
+}
+
+/* minifying */
+
+// the program as short as it can be written without changing it on the given tape: no comments or
+// whitespace, and no `+-` or `<>` pairs where they can't hit the limits of a cell or of the tape
+pub fn minify(commands: &[Command], config: &TapeConfig) -> String {
+	let wrapping = config.overflow == Overflow::Wrapping;
+	// a growable tape only ends on the left
+	let (left, right) = (config.wrap_pointer, config.wrap_pointer || config.growable);
+	let mut out: Vec<u8> = Vec::new();
+
+	for c in instructions(commands) {
+		match (out.last(), c) {
+			(Some(b'+'), b'-') | (Some(b'-'), b'+') if wrapping => { out.pop(); },
+			(Some(b'<'), b'>') if left => { out.pop(); },
+			(Some(b'>'), b'<') if right => { out.pop(); },
+			_ => out.push(c)
+		}
+	}
+
+	String::from_utf8(out).unwrap()
+}
+
+// like minify, but works on source so that its input is kept as it is
+pub fn minify_source(source: &[u8], dialect: &Dialect, config: &TapeConfig) -> Result<String, ParseError> {
+	let mut minified = minify(&source.parse_with(dialect)?, config);
+	if let (_, Some(input)) = dialect.split_input(source) {
+		minified.push('!');
+		minified.push_str(&String::from_utf8_lossy(input));
+	}
+
+	Ok(minified)
+}
+
+/* formatting */
+
+// lays the program out with every loop (and procedure) on its own lines, indented by its nesting
+pub fn format(commands: &[Command], style: &Style) -> String {
+	let source = instructions(commands);
+	let tokens: Vec<Token> = source.iter().map(|&c| Token::Instruction(c)).collect();
+
+	layout(&tokens, style)
+}
+
+// like format, but works on source so that its comments (and its input) can be kept
+pub fn format_source(source: &[u8], dialect: &Dialect, style: &Style) -> Result<String, ParseError> {
+	source.parse_with(dialect)?;
+
+	let (code, input) = dialect.split_input(source);
+	let mut formatted = layout(&tokenize(code, dialect), style);
+	if let Some(input) = input {
+		formatted.push('!');
+		formatted.push_str(&String::from_utf8_lossy(input));
+	}
+
+	Ok(formatted)
+}
+
+fn tokenize<'a>(source: &'a [u8], dialect: &Dialect) -> Vec<Token<'a>> {
+	let mut tokens = Vec::new();
+	let mut comment_start = None;
+
+	for (i, &c) in source.iter().enumerate() {
+		if dialect.is_instruction(c) {
+			if let Some(start) = comment_start.take() {
+				tokens.push(Token::Comment(&source[start..i]));
+			}
+			tokens.push(Token::Instruction(c));
+		} else if comment_start.is_none() {
+			comment_start = Some(i);
+		}
+	}
+	if let Some(start) = comment_start {
+		tokens.push(Token::Comment(&source[start..]));
+	}
+
+	tokens
+}
+
+fn layout(tokens: &[Token], style: &Style) -> String {
+	let mut out = String::new();
+	let mut line = String::new();
+	let mut depth = 0;
+
+	for token in tokens {
+		match *token {
+			Token::Instruction(c @ b'[') | Token::Instruction(c @ b'(') => {
+				push_line(&mut out, &mut line, depth, style);
+				line.push(c as char);
+				push_line(&mut out, &mut line, depth, style);
+				depth += 1;
+			},
+			Token::Instruction(c @ b']') | Token::Instruction(c @ b')') => {
+				push_line(&mut out, &mut line, depth, style);
+				depth -= 1;
+				line.push(c as char);
+				push_line(&mut out, &mut line, depth, style);
+			},
+			Token::Instruction(c) => {
+				if line.len() >= style.width { push_line(&mut out, &mut line, depth, style); }
+				line.push(c as char);
+			},
+			Token::Comment(text) if style.comments => {
+				let text = String::from_utf8_lossy(text);
+				let mut lines = text.lines();
+
+				// a comment on the same line as the code before it stays there
+				let first = lines.next().unwrap_or("").trim();
+				if !first.is_empty() {
+					if !line.is_empty() { line.push(' '); }
+					line.push_str(first);
+				}
+				push_line(&mut out, &mut line, depth, style);
+
+				for comment in lines.map(str::trim).filter(|comment| !comment.is_empty()) {
+					line.push_str(comment);
+					push_line(&mut out, &mut line, depth, style);
+				}
+			},
+			Token::Comment(_) => {}
+		}
+	}
+	push_line(&mut out, &mut line, depth, style);
+
+	out
+}
+
+fn push_line(out: &mut String, line: &mut String, depth: usize, style: &Style) {
+	if line.is_empty() { return; }
+
+	for _ in 0..depth { out.push_str(&style.indent); }
+	out.push_str(line);
+	out.push('\n');
+	line.clear();
+}
+
+/* pretty-printing */
+
+impl fmt::Display for Program {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&format(self.commands(), &Style::default()))
+	}
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use super::{format, format_source, minify, minify_source, Style};
+	use super::super::{Dialect, ParseBrainfuck, Program, HELLOWORLD};
+	use super::super::config::{Overflow, TapeConfig};
+
+	fn style() -> Style {
+		Style { indent: "  ".to_owned(), width: 8, comments: true }
+	}
+
+	#[test]
+	fn minifying() {
+		let wrapping = TapeConfig { wrap_pointer: true, ..TapeConfig::default() };
+		assert_eq!(minify(&b"a+ +-b\n>[<>-]<.".as_ref().parse().unwrap(), &wrapping), "+>[-]<.");
+		assert_eq!(minify(&b"+<>-[+-]".as_ref().parse().unwrap(), &wrapping), "[]");
+		assert_eq!(minify(&b"+[-]-".as_ref().parse().unwrap(), &wrapping), "+[-]-");
+
+		// the input isn't code, so it's left alone, '!'s and all
+		let dialect = Dialect { input_separator: true, ..Dialect::default() };
+		assert_eq!(minify_source(b"+- ,.[-] echo!a +- b!\n", &dialect, &wrapping), Ok(",.[-]!a +- b!\n".to_owned()));
+		assert_eq!(minify_source(b",.!", &dialect, &wrapping), Ok(",.!".to_owned()));
+		assert!(minify_source(b"[!]", &dialect, &wrapping).is_err());
+	}
+
+	#[test]
+	fn minifying_for_tape_limits() {
+		let program = b"-+<>><".as_ref().parse().unwrap();
+		// '<' fails on the first cell, '>' on the last one, and '-' on a cell at zero
+		assert_eq!(minify(&program, &TapeConfig::default()), "<>><");
+		assert_eq!(minify(&program, &TapeConfig { growable: true, ..TapeConfig::default() }), "<>");
+		assert_eq!(minify(&program, &TapeConfig { overflow: Overflow::Checked, wrap_pointer: true, ..TapeConfig::default() }), "-+");
+	}
+
+	#[test]
+	fn formatting() {
+		let program = b"++++++++++[>+++++++>++++++++++<<-]>++.".as_ref().parse().unwrap();
+
+		assert_eq!(format(&program, &style()), "\
+++++++++
+++
+[
+  >+++++++
+  >+++++++
+  +++<<-
+]
+>++.
+");
+	}
+
+	#[test]
+	fn keeping_comments() {
+		let source = b"print a newline\n++++++++++ ten\n.\n[-] and clear it\n  afterwards";
+		let formatted = format_source(source, &Dialect::default(), &Style { width: 64, ..style() }).unwrap();
+
+		assert_eq!(formatted, "print a newline\n++++++++++ ten\n.\n[\n  -\n]\nand clear it\nafterwards\n");
+		assert_eq!(format_source(formatted.as_bytes(), &Dialect::default(), &Style { width: 64, ..style() }), Ok(formatted));
+	}
+
+	#[test]
+	fn stripping_comments() {
+		let style = Style { comments: false, ..style() };
+
+		assert_eq!(format_source(b"+ one [- loop]", &Dialect::default(), &style), Ok("+\n[\n  -\n]\n".to_owned()));
+	}
+
+	#[test]
+	fn formatting_extensions() {
+		let dialect = Dialect { procedures: true, input_separator: true, ..Dialect::default() };
+		let formatted = format_source(b"(.,) print:!input", &dialect, &style()).unwrap();
+
+		assert_eq!(formatted, "(\n  .,\n)\nprint\n:\n!input");
+		assert!(format_source(b"(]", &dialect, &style()).is_err());
+	}
+
+	#[test]
+	fn pretty_printing() {
+		let program = Program::parse(HELLOWORLD).unwrap();
+		let printed = program.to_string();
+
+		assert!(printed.starts_with("++++++++++\n[\n\t>+++++++>++++++++++>+++>+<<<<-\n]\n"));
+		assert_eq!(Program::parse(printed.as_bytes()), Ok(program));
+	}
+}
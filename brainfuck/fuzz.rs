@@ -0,0 +1,346 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{self, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use super::{instructions, ir, Command, Interpreter, Program, RuntimeError};
+use super::Command::*;
+use super::RuntimeErrorKind::*;
+use super::budget::Budget;
+use super::config::{Overflow, TapeConfig};
+use super::transpile::{self, Target};
+use super::vm::Bytecode;
+
+/* random programs */
+
+// xorshift64*, so that a seed gives the same programs everywhere
+pub struct Rng {
+	state: u64
+}
+
+impl Rng {
+	pub fn new(seed: u64) -> Rng {
+		// a zero state would stay zero
+		Rng { state: seed ^ 0x9e37_79b9_7f4a_7c15 }
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.state ^= self.state >> 12;
+		self.state ^= self.state << 25;
+		self.state ^= self.state >> 27;
+		self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+	}
+
+	pub fn below(&mut self, n: u64) -> u64 { self.next_u64() % n }
+}
+
+// a random program of about `length` commands, with loops nested at most `depth` deep; half of the
+// loops start by decrementing their cell, so that more of them end
+pub fn random_program(rng: &mut Rng, length: usize, depth: usize) -> Vec<Command> {
+	let mut commands = Vec::new();
+
+	while commands.len() < length {
+		commands.push(match rng.below(if depth > 0 { 16 } else { 14 }) {
+			0..=3 => IncrementByte,
+			4..=5 => DecrementByte,
+			6..=8 => IncrementPos,
+			9..=10 => DecrementPos,
+			11..=12 => OutputByte,
+			13 => InputByte,
+			_ => {
+				let mut body = if rng.below(2) == 0 { vec![DecrementByte] } else { Vec::new() };
+				let body_length = rng.below(length as u64 / 2 + 1) as usize;
+				body.extend(random_program(rng, body_length, depth - 1));
+				Loop(body)
+			}
+		});
+	}
+
+	commands
+}
+
+/* running */
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Backend {
+	// the optimized ops
+	Ir,
+	Bytecode,
+	// the bytecode, stopped halfway and resumed from a snapshot
+	Resumed,
+	// the transpiled program, compiled with cc or rustc
+	Transpiled(Target)
+}
+
+pub const IN_PROCESS: &[Backend] = &[Backend::Ir, Backend::Bytecode, Backend::Resumed];
+
+// what a successful run left behind; the tape of transpiled programs can't be seen
+#[derive(Debug, PartialEq, Clone)]
+pub struct Outcome {
+	pub output: Vec<u8>,
+	pub tape: Option<(Vec<i64>, usize)>
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+	pub backend: Backend,
+	pub source: String,
+	pub input: Vec<u8>,
+	// the outcome of running the program as written, and whatever the backend did instead
+	pub expected: Result<Outcome, String>,
+	pub actual: Result<Outcome, String>
+}
+
+impl fmt::Display for Mismatch {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{:?} disagrees with the unoptimized program", self.backend)?;
+		writeln!(f, "program: {}", self.source)?;
+		writeln!(f, "input: {:?}", self.input)?;
+		writeln!(f, "expected: {:?}", self.expected)?;
+		write!(f, "actual: {:?}", self.actual)
+	}
+}
+
+type Run = (Result<(), RuntimeError>, Interpreter<io::Cursor<Vec<u8>>, Vec<u8>>);
+
+fn run_with<F: FnOnce(&mut Interpreter<io::Cursor<Vec<u8>>, Vec<u8>>) -> Result<(), RuntimeError>>(
+	input: &[u8], config: &TapeConfig, max_steps: u64, run: F) -> Run
+{
+	let mut interpreter = Interpreter::new(io::Cursor::new(input.to_vec()), Vec::new(), *config);
+	interpreter.set_budget(Budget { max_steps: Some(max_steps), ..Budget::unlimited() });
+	let result = run(&mut interpreter);
+
+	(result, interpreter)
+}
+
+fn outcome((result, interpreter): Run) -> Result<Outcome, String> {
+	result.map_err(|e| e.to_string())?;
+
+	// the moves the optimizer drops may have grown a growable tape further, which only adds zeros
+	let pos = interpreter.tape().pos();
+	let mut cells = interpreter.tape().cells().to_vec();
+	while cells.len() > pos + 1 && cells.last() == Some(&0) {
+		cells.pop();
+	}
+
+	Ok(Outcome {
+		tape: Some((cells, pos)),
+		output: interpreter.into_output()
+	})
+}
+
+// runs the program as written and, if it ends on its own within the given number of steps, with
+// every backend too; returns whether it did, or where a backend went a different way
+pub fn check(program: &[Command], input: &[u8], config: &TapeConfig, max_steps: u64, backends: &[Backend])
+	-> Result<bool, Box<Mismatch>>
+{
+	let written = Program::new(program.to_vec());
+	let expected = match run_with(input, config, max_steps, |i| i.run_unoptimized(&written)) {
+		// the optimized backends only promise the same results for programs that work, though unless
+		// the cells wrap around, they mustn't end the programs that overflow or run out of steps either
+		(Err(ref e), _) if config.overflow == Overflow::Wrapping || !matches!(e.kind, CellOverflow | StepLimitExceeded) =>
+			return Ok(false),
+		run => outcome(run)
+	};
+
+	let ops = ir::optimize(program, config.overflow);
+	let bytecode = Bytecode::compile(&instructions(program), config.overflow).unwrap();
+
+	for &backend in backends {
+		// the optimized programs never take more steps than the one as written
+		let actual = match backend {
+			Backend::Ir => outcome(run_with(input, config, max_steps, |i| i.run_ir(&ops))),
+			Backend::Bytecode => outcome(run_with(input, config, max_steps, |i| i.run_bytecode(&bytecode))),
+			Backend::Resumed => resumed(&bytecode, input, config, max_steps),
+			Backend::Transpiled(target) => transpiled(&ops, input, config, target)
+				.map(|output| Outcome { output: output, tape: None })
+		};
+
+		let expected = match (&expected, &actual) {
+			// without folded loops, the backends run at most one step for every instruction of the
+			// program, so what they end within the budget ends within that many times more steps
+			(&Err(_), &Ok(_)) => outcome(run_with(input, config, max_steps * instructions(program).len() as u64,
+				|i| i.run_unoptimized(&written))),
+			_ => expected.clone()
+		};
+		let matches = match (&expected, &actual) {
+			(Ok(expected), Ok(actual)) =>
+				actual.output == expected.output && (actual.tape.is_none() || actual.tape == expected.tape),
+			(Err(_), Err(_)) => true,
+			_ => false
+		};
+		if !matches {
+			return Err(Box::new(Mismatch {
+				backend: backend,
+				source: String::from_utf8(instructions(program)).unwrap(),
+				input: input.to_vec(),
+				expected: expected,
+				actual: actual
+			}));
+		}
+	}
+
+	Ok(expected.is_ok())
+}
+
+fn resumed(bytecode: &Bytecode, input: &[u8], config: &TapeConfig, max_steps: u64) -> Result<Outcome, String> {
+	let (_, full) = run_with(input, config, max_steps, |i| i.run_bytecode(bytecode));
+	let halfway = full.tape().steps() / 2;
+
+	let (result, stopped) = run_with(input, config, halfway, |i| i.run_bytecode(bytecode));
+	let snapshot = match result {
+		// only programs without a single instruction have no half
+		Ok(()) => return outcome((result, stopped)),
+		Err(e) => stopped.snapshot(bytecode, &e, stopped.output()).ok_or_else(|| e.to_string())?
+	};
+
+	outcome(run_with(input, config, max_steps, |i| i.resume(bytecode, &snapshot)))
+}
+
+/* transpiled programs */
+
+// tells apart the programs compiled at the same time
+static COMPILED: AtomicUsize = AtomicUsize::new(0);
+
+// compiles the transpiled program in a temporary directory and runs it
+fn transpiled(ops: &[ir::Op], input: &[u8], config: &TapeConfig, target: Target) -> Result<Vec<u8>, String> {
+	let name = format!("brainfuck-fuzz-{}-{}", process::id(), COMPILED.fetch_add(1, Ordering::Relaxed));
+	let binary = env::temp_dir().join(&name);
+	let source = binary.with_extension(match target {
+		Target::C => "c",
+		Target::Rust => "rs"
+	});
+
+	fs::write(&source, transpile::transpile(ops, config, target)).map_err(|e| e.to_string())?;
+	let result = compile(target, &source, &binary).and_then(|_| execute(&binary, input));
+	let _ = fs::remove_file(&source);
+	let _ = fs::remove_file(&binary);
+
+	result
+}
+
+fn compile(target: Target, source: &Path, binary: &Path) -> Result<(), String> {
+	let compiler = match target {
+		Target::C => "cc",
+		Target::Rust => "rustc"
+	};
+	let output = process::Command::new(compiler).arg("-o").arg(binary).arg(source).output()
+		.map_err(|e| format!("unable to run {}: {}", compiler, e))?;
+
+	if output.status.success() {
+		Ok(())
+	} else {
+		Err(String::from_utf8_lossy(&output.stderr).into_owned())
+	}
+}
+
+fn execute(binary: &Path, input: &[u8]) -> Result<Vec<u8>, String> {
+	let mut child = process::Command::new(binary)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.map_err(|e| e.to_string())?;
+	// the program may well end without reading all of its input
+	let _ = child.stdin.take().unwrap().write_all(input);
+	let output = child.wait_with_output().map_err(|e| e.to_string())?;
+
+	if output.status.success() {
+		Ok(output.stdout)
+	} else {
+		Err(String::from_utf8_lossy(&output.stderr).into_owned())
+	}
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use std::process;
+	use super::{check, random_program, Backend, Rng, IN_PROCESS};
+	use super::super::ParseBrainfuck;
+	use super::super::config::{CellWidth, EofPolicy, Overflow, TapeConfig};
+	use super::super::transpile::Target;
+
+	const MAX_STEPS: u64 = 10_000;
+
+	fn configs() -> Vec<TapeConfig> {
+		let classic = TapeConfig { cells: 64, eof: EofPolicy::Zero, ..TapeConfig::classic() };
+
+		vec![
+			classic,
+			TapeConfig { width: CellWidth::I64, ..classic },
+			TapeConfig { wrap_pointer: true, eof: EofPolicy::MinusOne, ..classic },
+			TapeConfig { growable: true, width: CellWidth::U16, ..classic },
+			TapeConfig { overflow: Overflow::Saturating, ..classic },
+			TapeConfig { overflow: Overflow::Checked, eof: EofPolicy::MinusOne, ..classic }
+		]
+	}
+
+	#[test]
+	fn random_programs() {
+		let mut rng = Rng::new(2018);
+		let mut checked = 0;
+
+		for _ in 0..2000 {
+			let length = 1 + rng.below(40) as usize;
+			let program = random_program(&mut rng, length, 3);
+			let input: Vec<u8> = (0..rng.below(8)).map(|_| rng.next_u64() as u8).collect();
+
+			for config in configs() {
+				match check(&program, &input, &config, MAX_STEPS, IN_PROCESS) {
+					Ok(true) => checked += 1,
+					Ok(false) => {},
+					Err(mismatch) => panic!("{}", mismatch)
+				}
+			}
+		}
+
+		// most random programs fail or run for too long, but enough of them don't
+		assert!(checked > 1000, "only {} runs were compared", checked);
+	}
+
+	#[test]
+	fn catching_differences() {
+		// the optimizer assumes `<>` doesn't leave the tape, so a program relying on that isn't compared
+		let program = b"<>+.".as_ref().parse().unwrap();
+		assert_eq!(check(&program, b"", &configs()[0], MAX_STEPS, IN_PROCESS), Ok(false));
+
+		let program = b"+[->+<]>.".as_ref().parse().unwrap();
+		assert_eq!(check(&program, b"", &configs()[0], MAX_STEPS, IN_PROCESS), Ok(true));
+
+		// programs that overflow or don't end within the budget are only compared when the cells don't wrap
+		let program = b"+[+]".as_ref().parse().unwrap();
+		for config in &configs()[4..] {
+			assert_eq!(check(&program, b"", config, MAX_STEPS, IN_PROCESS), Ok(false));
+		}
+	}
+
+	// runs the system's compilers, so only when asked to: cargo test -- --ignored
+	#[test]
+	#[ignore]
+	fn transpiled_programs() {
+		let mut rng = Rng::new(18);
+		let mut backends = Vec::new();
+		if process::Command::new("cc").arg("--version").output().is_ok() {
+			backends.push(Backend::Transpiled(Target::C));
+		}
+		if process::Command::new("rustc").arg("--version").output().is_ok() {
+			backends.push(Backend::Transpiled(Target::Rust));
+		}
+
+		// compiling is slow, so only a few of the programs that end are checked
+		let mut checked = 0;
+		while checked < 4 && !backends.is_empty() {
+			let program = random_program(&mut rng, 30, 2);
+			let config = configs()[checked % 4];
+			match check(&program, b"input", &config, MAX_STEPS, &backends) {
+				Ok(true) => checked += 1,
+				Ok(false) => {},
+				Err(mismatch) => panic!("{}", mismatch)
+			}
+		}
+	}
+}
@@ -0,0 +1,175 @@
+/* generating */
+
+// the ways of writing a program printing some text; the programs don't depend on cells wrapping
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Strategy {
+	// a single cell, incremented or decremented from one character to the next
+	Increments,
+	// a single cell, with larger changes made by a multiplication loop using the cell to its right
+	Multiplication,
+	// a loop setting up a cell near every group of characters first, then using the closest one
+	Cells
+}
+
+pub const STRATEGIES: &[Strategy] = &[Strategy::Increments, Strategy::Multiplication, Strategy::Cells];
+
+pub fn generate(text: &[u8], strategy: Strategy) -> String {
+	match strategy {
+		Strategy::Increments => increments(text),
+		Strategy::Multiplication => multiplication(text),
+		Strategy::Cells => cells(text)
+	}
+}
+
+// the shortest of the programs generated with every strategy
+pub fn shortest(text: &[u8]) -> String {
+	STRATEGIES.iter().map(|&strategy| generate(text, strategy)).min_by_key(String::len).unwrap()
+}
+
+fn increments(text: &[u8]) -> String {
+	let mut out = String::new();
+	let mut value = 0;
+
+	for &c in text {
+		adjust(&mut out, c as i64 - value);
+		out.push('.');
+		value = c as i64;
+	}
+
+	out
+}
+
+fn multiplication(text: &[u8]) -> String {
+	let mut out = String::new();
+	let mut value = 0;
+
+	for &c in text {
+		let delta = c as i64 - value;
+		match factors(delta.abs()) {
+			Some((a, b, rest)) => {
+				let sign = if delta < 0 { '-' } else { '+' };
+				out.push('>');
+				repeat(&mut out, '+', a);
+				out.push_str("[<");
+				repeat(&mut out, sign, b);
+				out.push_str(">-]<");
+				adjust(&mut out, delta.signum() * rest);
+			},
+			None => adjust(&mut out, delta)
+		}
+		out.push('.');
+		value = c as i64;
+	}
+
+	out
+}
+
+// the shortest way of writing n as a * b + rest, if a multiplication loop is shorter than n increments
+fn factors(n: i64) -> Option<(i64, i64, i64)> {
+	// the loop itself takes six characters: '>', "[<", ">-]<"
+	let cost = |&(a, b, rest): &(i64, i64, i64)| a + b + rest.abs() + 6;
+
+	(2..n).flat_map(|a| {
+		let b = n / a;
+		vec![(a, b, n - a * b), (a, b + 1, n - a * (b + 1))]
+	}).filter(|candidate| cost(candidate) < n).min_by_key(cost)
+}
+
+fn cells(text: &[u8]) -> String {
+	if text.is_empty() { return String::new(); }
+
+	(2..17).map(|factor| cells_with_factor(text, factor)).min_by_key(String::len).unwrap()
+}
+
+// the first cell counts the iterations of a loop adding `factor` times the nearest multiple
+// to every other cell, one for every multiple close to a character of the text
+fn cells_with_factor(text: &[u8], factor: i64) -> String {
+	let mut multiples: Vec<i64> = text.iter().map(|&c| (c as i64 + factor / 2) / factor).collect();
+	multiples.sort_unstable();
+	multiples.dedup();
+
+	let mut out = String::new();
+	repeat(&mut out, '+', factor);
+	out.push('[');
+	for &multiple in &multiples {
+		out.push('>');
+		repeat(&mut out, '+', multiple);
+	}
+	repeat(&mut out, '<', multiples.len() as i64);
+	out.push_str("-]");
+
+	// the cells' values, counting the first one
+	let mut values: Vec<i64> = Some(0).into_iter().chain(multiples.iter().map(|m| m * factor)).collect();
+	let mut pos = 0;
+	for &c in text {
+		let c = c as i64;
+		let cost = |i: usize| (i as i64 - pos as i64).abs() + (values[i] - c).abs();
+		let nearest = (0..values.len()).min_by_key(|&i| cost(i)).unwrap();
+
+		let (direction, distance) = if nearest < pos { ('<', pos - nearest) } else { ('>', nearest - pos) };
+		repeat(&mut out, direction, distance as i64);
+		adjust(&mut out, c - values[nearest]);
+		out.push('.');
+		values[nearest] = c;
+		pos = nearest;
+	}
+
+	out
+}
+
+fn adjust(out: &mut String, delta: i64) {
+	repeat(out, if delta < 0 { '-' } else { '+' }, delta.abs());
+}
+
+fn repeat(out: &mut String, c: char, n: i64) {
+	for _ in 0..n { out.push(c); }
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use super::{generate, shortest, Strategy, STRATEGIES};
+	use super::super::{Interpreter, Program};
+	use super::super::config::{CellWidth, TapeConfig};
+
+	fn output(program: &str, config: TapeConfig) -> Vec<u8> {
+		let mut interpreter = Interpreter::new(io::empty(), Vec::new(), config);
+		interpreter.run(&program.parse::<Program>().unwrap()).unwrap();
+		interpreter.into_output()
+	}
+
+	#[test]
+	fn generating() {
+		assert_eq!(generate(b"ABA", Strategy::Increments), format!("{}.+.-.", "+".repeat(65)));
+		assert_eq!(generate(b"\n", Strategy::Multiplication), "++++++++++.");
+		assert_eq!(generate(b"(", Strategy::Multiplication), ">+++++[<++++++++>-]<.");
+		assert_eq!(generate(b"", Strategy::Cells), "");
+	}
+
+	#[test]
+	fn printing_the_text() {
+		let all_bytes: Vec<u8> = (0..=255).collect();
+		let texts: &[&[u8]] = &[b"", b"Hello World!\n", b"zzz  aaa", b"\x00\xff\x00", &all_bytes];
+		// the programs don't rely on cells wrapping around
+		let wide = TapeConfig { width: CellWidth::I64, ..TapeConfig::classic() };
+
+		for &text in texts {
+			for &strategy in STRATEGIES {
+				let program = generate(text, strategy);
+				assert_eq!(output(&program, TapeConfig::classic()), text, "{:?}", strategy);
+				assert_eq!(output(&program, wide), text, "{:?}", strategy);
+			}
+		}
+	}
+
+	#[test]
+	fn comparing_strategies() {
+		let text = b"Hello World!\n";
+		let lengths: Vec<usize> = STRATEGIES.iter().map(|&strategy| generate(text, strategy).len()).collect();
+
+		assert!(lengths[0] > lengths[1] && lengths[1] > lengths[2], "{:?}", lengths);
+		assert_eq!(shortest(text).len(), lengths[2]);
+	}
+}
@@ -0,0 +1,95 @@
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+typedef uint8_t cell;
+
+static cell *tape;
+static size_t len = 30000;
+static size_t pos = 0;
+
+static void fail(const char *message) {
+	fflush(stdout);
+	fprintf(stderr, "error: %s\n", message);
+	exit(4);
+}
+
+static inline size_t offset(ptrdiff_t n) {
+	ptrdiff_t target = (ptrdiff_t)pos + n;
+
+	if (target >= 0 && (size_t)target < len) return (size_t)target;
+	fail(target < 0 ? "the pointer moved left of the first cell" : "the pointer moved past the last cell");
+	return 0;
+}
+
+static inline void add(size_t at, int64_t delta) {
+	int64_t value;
+
+	__builtin_add_overflow((int64_t)tape[at], delta, &value);
+	tape[at] = (cell)value;
+}
+
+static inline void mul_add(ptrdiff_t to, int64_t factor) {
+	int64_t delta;
+
+	if (!tape[pos]) return;
+	__builtin_mul_overflow((int64_t)tape[pos], factor, &delta);
+	add(offset(to), delta);
+}
+
+static inline void output(void) {
+	putchar((unsigned char)tape[pos]);
+}
+
+static inline void input(void) {
+	int c;
+
+	fflush(stdout);
+	c = getchar();
+	if (c != EOF) tape[pos] = (cell)c;
+}
+
+int main(void) {
+	tape = calloc(len, sizeof(cell));
+	if (!tape) fail("out of memory");
+
+	add(pos, 10);
+	mul_add(1, 7);
+	mul_add(2, 10);
+	mul_add(3, 3);
+	mul_add(4, 1);
+	tape[pos] = 0;
+	pos = offset(1);
+	add(pos, 2);
+	output();
+	pos = offset(1);
+	add(pos, 1);
+	output();
+	add(pos, 7);
+	output();
+	output();
+	add(pos, 3);
+	output();
+	pos = offset(1);
+	add(pos, 2);
+	output();
+	pos = offset(-2);
+	add(pos, 15);
+	output();
+	pos = offset(1);
+	output();
+	add(pos, 3);
+	output();
+	add(pos, -6);
+	output();
+	add(pos, -8);
+	output();
+	pos = offset(1);
+	add(pos, 1);
+	output();
+	pos = offset(1);
+	output();
+	fflush(stdout);
+	return 0;
+}
@@ -0,0 +1,115 @@
+#![allow(dead_code, unused_mut)]
+
+use std::io::{self, Read, Write};
+use std::process;
+
+type Cell = u8;
+
+struct Tape {
+	cells: Vec<Cell>,
+	pos: usize,
+	output: io::BufWriter<io::Stdout>
+}
+
+fn fail(message: &str) -> ! {
+	eprintln!("error: {}", message);
+	process::exit(4)
+}
+
+impl Tape {
+	fn offset(&mut self, n: isize) -> usize {
+		let len = self.cells.len() as isize;
+		let target = self.pos as isize + n;
+
+		if target >= 0 && target < len { return target as usize; }
+		self.output.flush().unwrap();
+		if target < 0 { fail("the pointer moved left of the first cell") }
+		fail("the pointer moved past the last cell")
+	}
+
+	fn add(&mut self, at: usize, delta: i64) {
+		let value = self.cells[at] as i64;
+		match Some(value.wrapping_add(delta)) {
+			Some(value) => self.cells[at] = value as Cell,
+			None => {
+				self.output.flush().unwrap();
+				fail("the cell value overflowed")
+			}
+		}
+	}
+
+	fn mul_add(&mut self, to: isize, factor: i64) {
+		let value = self.cells[self.pos] as i64;
+		if value == 0 { return; }
+		match Some(value.wrapping_mul(factor)) {
+			Some(delta) => {
+				let at = self.offset(to);
+				self.add(at, delta)
+			},
+			None => {
+				self.output.flush().unwrap();
+				fail("the cell value overflowed")
+			}
+		}
+	}
+
+	fn output(&mut self) {
+		self.output.write_all(&[self.cells[self.pos] as u8]).unwrap();
+	}
+
+	fn input(&mut self) {
+		let mut buf = [0];
+
+		self.output.flush().unwrap();
+		match io::stdin().read(&mut buf) {
+			Ok(1) => self.cells[self.pos] = buf[0] as Cell,
+			_ => {}
+		}
+	}
+}
+
+fn main() {
+	let mut t = Tape {
+		cells: vec![0; 30000],
+		pos: 0,
+		output: io::BufWriter::new(io::stdout())
+	};
+
+	t.add(t.pos, 10);
+	t.mul_add(1, 7);
+	t.mul_add(2, 10);
+	t.mul_add(3, 3);
+	t.mul_add(4, 1);
+	t.cells[t.pos] = 0;
+	t.pos = t.offset(1);
+	t.add(t.pos, 2);
+	t.output();
+	t.pos = t.offset(1);
+	t.add(t.pos, 1);
+	t.output();
+	t.add(t.pos, 7);
+	t.output();
+	t.output();
+	t.add(t.pos, 3);
+	t.output();
+	t.pos = t.offset(1);
+	t.add(t.pos, 2);
+	t.output();
+	t.pos = t.offset(-2);
+	t.add(t.pos, 15);
+	t.output();
+	t.pos = t.offset(1);
+	t.output();
+	t.add(t.pos, 3);
+	t.output();
+	t.add(t.pos, -6);
+	t.output();
+	t.add(t.pos, -8);
+	t.output();
+	t.pos = t.offset(1);
+	t.add(t.pos, 1);
+	t.output();
+	t.pos = t.offset(1);
+	t.output();
+	t.output.flush().unwrap();
+}
@@ -0,0 +1,91 @@
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+typedef uint16_t cell;
+
+static cell *tape;
+static size_t len = 1000;
+static size_t pos = 0;
+
+static void fail(const char *message) {
+	fflush(stdout);
+	fprintf(stderr, "error: %s\n", message);
+	exit(4);
+}
+
+static inline size_t offset(ptrdiff_t n) {
+	ptrdiff_t target = (ptrdiff_t)pos + n;
+
+	if (target >= 0 && (size_t)target < len) return (size_t)target;
+	if (target >= 0) {
+		cell *grown = realloc(tape, ((size_t)target + 1) * sizeof(cell));
+		if (!grown) fail("out of memory");
+		for (size_t i = len; i <= (size_t)target; i++) grown[i] = 0;
+		tape = grown;
+		len = (size_t)target + 1;
+		return (size_t)target;
+	}
+	fail(target < 0 ? "the pointer moved left of the first cell" : "the pointer moved past the last cell");
+	return 0;
+}
+
+static inline void add(size_t at, int64_t delta) {
+	int64_t value;
+
+	if (__builtin_add_overflow((int64_t)tape[at], delta, &value)) value = delta < 0 ? INT64_MIN : INT64_MAX;
+	if (value < 0) value = 0;
+	if (value > UINT16_MAX) value = UINT16_MAX;
+	tape[at] = (cell)value;
+}
+
+static inline void mul_add(ptrdiff_t to, int64_t factor) {
+	int64_t delta;
+
+	if (!tape[pos]) return;
+	if (__builtin_mul_overflow((int64_t)tape[pos], factor, &delta))
+		delta = ((int64_t)tape[pos] < 0) != (factor < 0) ? INT64_MIN : INT64_MAX;
+	add(offset(to), delta);
+}
+
+static inline void output(void) {
+	putchar((unsigned char)tape[pos]);
+}
+
+static inline void input(void) {
+	int c;
+
+	fflush(stdout);
+	c = getchar();
+	if (c != EOF) tape[pos] = (cell)c;
+	else fail("the input ran out");
+}
+
+int main(void) {
+	tape = calloc(len, sizeof(cell));
+	if (!tape) fail("out of memory");
+
+	input();
+	while (tape[pos]) {
+		add(pos, -1);
+		pos = offset(1);
+		add(pos, 2);
+		pos = offset(1);
+		add(pos, 1);
+		pos = offset(-2);
+	}
+	pos = offset(1);
+	while (tape[pos]) {
+		output();
+		pos = offset(1);
+	}
+	pos = offset(-2);
+	while (tape[pos]) {
+		add(pos, -1);
+	}
+	add(pos, 1);
+	while (tape[pos]) pos = offset(-1);
+	fflush(stdout);
+	return 0;
+}
@@ -0,0 +1,103 @@
+#![allow(dead_code, unused_mut)]
+
+use std::io::{self, Read, Write};
+use std::process;
+
+type Cell = u16;
+
+struct Tape {
+	cells: Vec<Cell>,
+	pos: usize,
+	output: io::BufWriter<io::Stdout>
+}
+
+fn fail(message: &str) -> ! {
+	eprintln!("error: {}", message);
+	process::exit(4)
+}
+
+impl Tape {
+	fn offset(&mut self, n: isize) -> usize {
+		let len = self.cells.len() as isize;
+		let target = self.pos as isize + n;
+
+		if target >= 0 && target < len { return target as usize; }
+		if target >= 0 {
+			self.cells.resize(target as usize + 1, 0);
+			return target as usize;
+		}
+		self.output.flush().unwrap();
+		if target < 0 { fail("the pointer moved left of the first cell") }
+		fail("the pointer moved past the last cell")
+	}
+
+	fn add(&mut self, at: usize, delta: i64) {
+		let value = self.cells[at] as i64;
+		match Some(value.saturating_add(delta)).map(|value| value.max(0).min(Cell::max_value() as i64)) {
+			Some(value) => self.cells[at] = value as Cell,
+			None => {
+				self.output.flush().unwrap();
+				fail("the cell value overflowed")
+			}
+		}
+	}
+
+	fn mul_add(&mut self, to: isize, factor: i64) {
+		let value = self.cells[self.pos] as i64;
+		if value == 0 { return; }
+		match Some(value.saturating_mul(factor)) {
+			Some(delta) => {
+				let at = self.offset(to);
+				self.add(at, delta)
+			},
+			None => {
+				self.output.flush().unwrap();
+				fail("the cell value overflowed")
+			}
+		}
+	}
+
+	fn output(&mut self) {
+		self.output.write_all(&[self.cells[self.pos] as u8]).unwrap();
+	}
+
+	fn input(&mut self) {
+		let mut buf = [0];
+
+		self.output.flush().unwrap();
+		match io::stdin().read(&mut buf) {
+			Ok(1) => self.cells[self.pos] = buf[0] as Cell,
+			_ => fail("the input ran out")
+		}
+	}
+}
+
+fn main() {
+	let mut t = Tape {
+		cells: vec![0; 1000],
+		pos: 0,
+		output: io::BufWriter::new(io::stdout())
+	};
+
+	t.input();
+	while t.cells[t.pos] != 0 {
+		t.add(t.pos, -1);
+		t.pos = t.offset(1);
+		t.add(t.pos, 2);
+		t.pos = t.offset(1);
+		t.add(t.pos, 1);
+		t.pos = t.offset(-2);
+	}
+	t.pos = t.offset(1);
+	while t.cells[t.pos] != 0 {
+		t.output();
+		t.pos = t.offset(1);
+	}
+	t.pos = t.offset(-2);
+	while t.cells[t.pos] != 0 {
+		t.add(t.pos, -1);
+	}
+	t.add(t.pos, 1);
+	while t.cells[t.pos] != 0 { t.pos = t.offset(-1); }
+	t.output.flush().unwrap();
+}
@@ -0,0 +1,308 @@
+use std::io::{Read, Write};
+use super::{Command, Interpreter, RuntimeError, RuntimeErrorKind};
+use super::config::Overflow;
+use self::Op::*;
+
+/* intermediate representation */
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Op {
+	Add(i64),
+	Move(isize),
+	SetZero,
+	// adds `factor` times the current cell to the cell at `offset`
+	MulAdd { offset: isize, factor: i64 },
+	// moves by the given step until a zero cell is found
+	Scan(isize),
+	Output,
+	Input,
+	Loop(Vec<Op>)
+}
+
+/* optimization */
+
+// the optimized ops give the same results as the commands on tapes with the given overflow mode:
+// only wrapping cells let `+-` cancel out and turn `[-]` and multiplication loops into single ops,
+// as saturating or checked cells could hit a limit halfway through them; runs like `><` that would
+// leave the tape halfway through pass unnoticed whatever the mode; pbrain procedures aren't
+// supported, as they are defined while the program runs
+pub fn optimize(commands: &[Command], overflow: Overflow) -> Vec<Op> {
+	let mut ops = Vec::new();
+
+	for command in commands {
+		match *command {
+			Command::IncrementPos => push_move(&mut ops, 1),
+			Command::DecrementPos => push_move(&mut ops, -1),
+			Command::IncrementByte => push_add(&mut ops, 1, overflow),
+			Command::DecrementByte => push_add(&mut ops, -1, overflow),
+			Command::OutputByte => ops.push(Output),
+			Command::InputByte => ops.push(Input),
+			Command::Debug => {},
+			Command::Procedure(..) | Command::Call => panic!("programs using procedures can't be optimized"),
+			Command::Loop(ref body) => ops.extend(optimize_loop(optimize(body, overflow), overflow))
+		}
+	}
+
+	ops
+}
+
+// whether adding n to an addition of m gives the same result as adding them one after the other;
+// a saturating or checked cell can only take a run of additions at once if they all go the same way
+pub fn folds(overflow: Overflow, m: i64, n: i64) -> bool {
+	overflow == Overflow::Wrapping || m.signum() == n.signum()
+}
+
+fn push_add(ops: &mut Vec<Op>, n: i64, overflow: Overflow) {
+	if let Some(&mut Add(ref mut m)) = ops.last_mut() {
+		if folds(overflow, *m, n) {
+			*m += n;
+			if *m == 0 { ops.pop(); }
+			return;
+		}
+	}
+	ops.push(Add(n));
+}
+
+fn push_move(ops: &mut Vec<Op>, n: isize) {
+	if let Some(&mut Move(ref mut m)) = ops.last_mut() {
+		*m += n;
+		if *m == 0 { ops.pop(); }
+		return;
+	}
+	ops.push(Move(n));
+}
+
+// the ops replacing a loop with the given body; shared with the bytecode compiler
+pub fn optimize_loop(body: Vec<Op>, overflow: Overflow) -> Vec<Op> {
+	match body[..] {
+		// a scan only moves the pointer, so it works the same whatever the cells do
+		[Move(n)] => return vec![Scan(n)],
+		// `[+]` never ends on a saturating cell and fails on a checked one, while the additions of a
+		// multiplication loop could hit a limit of their cells on the way
+		_ if overflow != Overflow::Wrapping => return vec![Loop(body)],
+		[Add(1)] | [Add(-1)] => return vec![SetZero],
+		_ => {}
+	}
+
+	match mul_add_loop(&body) {
+		Some(ops) => ops,
+		None => vec![Loop(body)]
+	}
+}
+
+// recognizes loops that only add to cells around the current one and return to it,
+// decrementing (or incrementing) the current cell by one per iteration
+fn mul_add_loop(body: &[Op]) -> Option<Vec<Op>> {
+	let mut offset = 0;
+	let mut deltas: Vec<(isize, i64)> = Vec::new();
+
+	for op in body {
+		match *op {
+			Add(n) => match deltas.iter_mut().find(|d| d.0 == offset) {
+				Some(delta) => delta.1 += n,
+				None => deltas.push((offset, n))
+			},
+			Move(n) => offset += n,
+			_ => return None
+		}
+	}
+
+	let step = deltas.iter().find(|d| d.0 == 0).map(|d| d.1);
+	if offset != 0 || (step != Some(-1) && step != Some(1)) { return None; }
+
+	// the number of iterations is the cell value when decrementing and its negation otherwise
+	let sign = -step.unwrap();
+	let mut ops: Vec<Op> = deltas.into_iter()
+		.filter(|&(offset, delta)| offset != 0 && delta != 0)
+		.map(|(offset, delta)| MulAdd { offset: offset, factor: delta * sign })
+		.collect();
+	ops.push(SetZero);
+
+	Some(ops)
+}
+
+impl Op {
+	// the number of instructions, counting both ends of loops
+	pub fn size(&self) -> usize {
+		match *self {
+			Loop(ref body) => 2 + body.iter().map(Op::size).sum::<usize>(),
+			_ => 1
+		}
+	}
+}
+
+/* execution */
+
+impl<R: Read, W: Write> Interpreter<R, W> {
+	// instruction positions and steps in errors refer to the ops rather than the source;
+	// every move of a scan counts as a separate step, as it can go on forever on a wrapping tape
+	pub fn run_ir(&mut self, ops: &[Op]) -> Result<(), RuntimeError> {
+		self.run_ir_from(ops, 0).map(|_| ())
+	}
+
+	fn run_ir_from(&mut self, ops: &[Op], mut pc: usize) -> Result<usize, RuntimeError> {
+		for op in ops.iter() {
+			self.step().map_err(|kind| self.error(kind, pc))?;
+			match *op {
+				Loop(ref body) => pc = self.run_ir_loop(body, pc)?,
+				ref op => self.execute_ir(op).map_err(|kind| self.error(kind, pc))?
+			}
+			pc += 1;
+		}
+
+		Ok(pc)
+	}
+
+	fn run_ir_loop(&mut self, body: &[Op], pc: usize) -> Result<usize, RuntimeError> {
+		let mut end = None;
+
+		while self.tape.cell() != 0 {
+			let close = self.run_ir_from(body, pc + 1)?;
+			self.step().map_err(|kind| self.error(kind, close))?;
+			end = Some(close);
+		}
+
+		Ok(end.unwrap_or_else(|| pc + 1 + body.iter().map(Op::size).sum::<usize>()))
+	}
+
+	fn execute_ir(&mut self, op: &Op) -> Result<(), RuntimeErrorKind> {
+		match *op {
+			Add(n) => self.tape.add(n as i128),
+			Move(n) => self.tape.move_by(n),
+			SetZero => {
+				self.tape.set_cell(0);
+				Ok(())
+			},
+			MulAdd { offset, factor } => {
+				// like the loop it replaces, it must not touch anything if the cell is zero
+				let value = self.tape.cell();
+				if value != 0 {
+					let target = self.tape.offset(offset)?;
+					self.tape.add_at(target, value as i128 * factor as i128)?;
+				}
+				Ok(())
+			},
+			Scan(n) => {
+				while self.tape.cell() != 0 {
+					self.tape.move_by(n)?;
+					self.step()?;
+				}
+				Ok(())
+			},
+			Output => self.output_byte(),
+			Input => self.input_byte(),
+			Loop(..) => unreachable!()
+		}
+	}
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use super::{optimize, Op};
+	use super::Op::*;
+	use super::super::{Interpreter, ParseBrainfuck, Program, FIZZBUZZ, HELLOWORLD};
+	use super::super::budget::Budget;
+	use super::super::config::{CellWidth, Overflow, TapeConfig};
+	use super::super::RuntimeErrorKind::PointerUnderflow;
+
+	fn optimized(source: &[u8]) -> Vec<Op> {
+		optimize(&source.parse().unwrap(), Overflow::Wrapping)
+	}
+
+	fn interpreter() -> Interpreter<io::Empty, io::Sink> {
+		Interpreter::new(io::empty(), io::sink(), TapeConfig::default())
+	}
+
+	fn assert_equivalent(source: &[u8]) {
+		let program = Program::parse(source).unwrap();
+		let mut naive = interpreter();
+		let mut optimized = interpreter();
+
+		naive.run_unoptimized(&program).unwrap();
+		optimized.run_ir(&optimize(program.commands(), Overflow::Wrapping)).unwrap();
+
+		assert_eq!(naive.tape.pos, optimized.tape.pos);
+		assert!(naive.tape.array[..] == optimized.tape.array[..]);
+	}
+
+	#[test]
+	fn folding_runs() {
+		assert_eq!(optimized(b"+++>>-<"), vec![Add(3), Move(2), Add(-1), Move(-1)]);
+		assert_eq!(optimized(b"++--+>><<<"), vec![Add(1), Move(-1)]);
+		assert_eq!(optimized(b">+-<."), vec![Output]);
+	}
+
+	#[test]
+	fn recognizing_idioms() {
+		assert_eq!(optimized(b"[-][+]"), vec![SetZero, SetZero]);
+		assert_eq!(optimized(b"[>>][<]"), vec![Scan(2), Scan(-1)]);
+		assert_eq!(optimized(b"[->++>+<<]"), vec![
+			MulAdd { offset: 1, factor: 2 },
+			MulAdd { offset: 2, factor: 1 },
+			SetZero
+		]);
+		assert_eq!(optimized(b"[<-->+]"), vec![MulAdd { offset: -1, factor: 2 }, SetZero]);
+		assert_eq!(optimized(b"[->+<.]"), vec![Loop(vec![Add(-1), Move(1), Add(1), Move(-1), Output])]);
+		assert_eq!(optimized(b"[-->+<]"), vec![Loop(vec![Add(-2), Move(1), Add(1), Move(-1)])]);
+	}
+
+	#[test]
+	fn equivalence_with_naive_interpreter() {
+		assert_equivalent(b"+++++[->++>+++<<]>[->+<]>>[-]<<<");
+		assert_equivalent(b">+>+>+>>+<<<<<[>]>[<+>-]++[>+++[>++<-]<-]");
+		assert_equivalent(HELLOWORLD);
+		assert_equivalent(FIZZBUZZ);
+	}
+
+	#[test]
+	fn overflow_modes() {
+		let source = b"+>+<";
+		assert_eq!(optimize(&b"++-[-][->++<]".as_ref().parse().unwrap(), Overflow::Saturating), vec![
+			Add(2),
+			Add(-1),
+			Loop(vec![Add(-1)]),
+			Loop(vec![Add(-1), Move(1), Add(2), Move(-1)])
+		]);
+		assert_eq!(optimize(&source.as_ref().parse().unwrap(), Overflow::Checked), optimized(source));
+		assert_eq!(optimize(&b"[>>]".as_ref().parse().unwrap(), Overflow::Checked), vec![Scan(2)]);
+
+		// programs whose cells hit their limits, which only wrapping cells may ignore
+		let programs: &[&[u8]] = &[
+			b"-+.>+[+]",
+			b">++++++++++++++++[<++++++++++++++++>-]<-[->+<]>+-.",
+			b">++++++++++++++++[<++++++++++++++++>-]<-----<+++[->++++>+<--<]>.>.",
+			HELLOWORLD
+		];
+		for &overflow in &[Overflow::Wrapping, Overflow::Saturating, Overflow::Checked] {
+			let config = TapeConfig { cells: 16, width: CellWidth::U8, overflow: overflow, ..TapeConfig::default() };
+
+			for (i, &source) in programs.iter().enumerate() {
+				let program = Program::parse(source).unwrap();
+				let mut naive = Interpreter::new(io::empty(), Vec::new(), config);
+				let mut optimized = Interpreter::new(io::empty(), Vec::new(), config);
+				naive.set_budget(Budget { max_steps: Some(100_000), ..Budget::unlimited() });
+				optimized.set_budget(Budget { max_steps: Some(100_000), ..Budget::unlimited() });
+
+				let naive_result = naive.run_unoptimized(&program);
+				let optimized_result = optimized.run_ir(&optimize(program.commands(), overflow));
+				// the errors happen at other positions, but they're the same errors
+				assert_eq!(naive_result.map_err(|e| e.kind), optimized_result.map_err(|e| e.kind), "{:?} {}", overflow, i);
+				if naive_result.is_ok() {
+					assert_eq!(naive.output(), optimized.output(), "{:?} {}", overflow, i);
+					assert_eq!(naive.tape.array, optimized.tape.array, "{:?} {}", overflow, i);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn error_positions() {
+		let error = interpreter().run_ir(&optimized(b"+[-]+[>+<-]<")).unwrap_err();
+
+		assert_eq!(error.kind, PointerUnderflow);
+		assert_eq!((error.position, error.steps), (5, 6));
+	}
+}
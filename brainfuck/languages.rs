@@ -0,0 +1,209 @@
+use std::error::Error;
+use std::fmt;
+use super::{instructions, Command, ParseBrainfuck, ParseError, Position};
+use super::Command::*;
+use super::ParseError::*;
+
+// the most instructions put on a single line when writing a program
+const PER_LINE: usize = 8;
+
+/* languages */
+
+// a language spelling brainfuck's instructions with words separated by whitespace
+#[derive(Debug, PartialEq)]
+pub struct Language {
+	pub name: &'static str,
+	// the words of '>', '<', '+', '-', '.', ',', '[' and ']', in that order
+	pub instructions: [&'static str; 8]
+}
+
+pub const OOK: Language = Language {
+	name: "ook",
+	instructions: ["Ook. Ook?", "Ook? Ook.", "Ook. Ook.", "Ook! Ook!", "Ook! Ook.", "Ook. Ook!", "Ook! Ook?", "Ook? Ook!"]
+};
+
+pub const BLUB: Language = Language {
+	name: "blub",
+	instructions: ["Blub. Blub?", "Blub? Blub.", "Blub. Blub.", "Blub! Blub!", "Blub! Blub.", "Blub. Blub!", "Blub! Blub?",
+		"Blub? Blub!"]
+};
+
+pub const PIKALANG: Language = Language {
+	name: "pikalang",
+	instructions: ["pipi", "pichu", "pi", "ka", "pikachu", "pikapi", "pika", "chu"]
+};
+
+pub const LANGUAGES: &[&Language] = &[&OOK, &BLUB, &PIKALANG];
+
+pub fn by_name(name: &str) -> Option<&'static Language> {
+	LANGUAGES.iter().cloned().find(|language| language.name == name)
+}
+
+const BRAINFUCK: &[u8; 8] = b"><+-.,[]";
+
+#[derive(Debug, PartialEq)]
+pub enum LanguageError {
+	// the word that doesn't start any instruction, or is missing the ones completing it
+	UnknownWord(String, Position),
+	Parse(ParseError)
+}
+
+impl fmt::Display for LanguageError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			LanguageError::UnknownWord(ref word, position) => write!(f, "unknown instruction '{}' at {}", word, position),
+			LanguageError::Parse(ref e) => write!(f, "{}", e)
+		}
+	}
+}
+
+impl Error for LanguageError {}
+
+/* translating */
+
+// the words of the source and where they start
+fn words(source: &[u8]) -> Vec<(&[u8], Position)> {
+	let mut words = Vec::new();
+	let mut position = Position { offset: 0, line: 1, column: 1 };
+	let mut start = None;
+
+	for (offset, &c) in source.iter().enumerate() {
+		position.offset = offset;
+		match (c.is_ascii_whitespace(), start) {
+			(true, Some((first, at))) => {
+				words.push((&source[first..offset], at));
+				start = None;
+			},
+			(false, None) => start = Some((offset, position)),
+			_ => {}
+		}
+		position.advance(c);
+	}
+	if let Some((first, at)) = start {
+		words.push((&source[first..], at));
+	}
+
+	words
+}
+
+// the brainfuck instructions of the source, along with the position of each of them
+fn translate(source: &[u8], language: &Language) -> Result<(Vec<u8>, Vec<Position>), LanguageError> {
+	let words = words(source);
+	// the longest instructions go first, so that none of them is taken for another one's start
+	let mut candidates: Vec<(Vec<&[u8]>, u8)> = language.instructions.iter().zip(BRAINFUCK.iter())
+		.map(|(instruction, &c)| (instruction.split_whitespace().map(str::as_bytes).collect(), c))
+		.collect();
+	candidates.sort_by_key(|(words, _)| usize::MAX - words.len());
+
+	let mut code = Vec::new();
+	let mut positions = Vec::new();
+	let mut i = 0;
+	while i < words.len() {
+		let rest = &words[i..];
+		let found = candidates.iter().find(|(expected, _)| {
+			expected.len() <= rest.len() && expected.iter().zip(rest).all(|(&expected, &(word, _))| expected == word)
+		});
+
+		match found {
+			Some((expected, c)) => {
+				code.push(*c);
+				positions.push(rest[0].1);
+				i += expected.len();
+			},
+			None => {
+				let (word, position) = rest[0];
+				return Err(LanguageError::UnknownWord(String::from_utf8_lossy(word).into_owned(), position));
+			}
+		}
+	}
+
+	Ok((code, positions))
+}
+
+pub fn parse(source: &[u8], language: &Language) -> Result<Vec<Command>, LanguageError> {
+	let (code, positions) = translate(source, language)?;
+
+	// the translated code has an instruction per byte, so its offsets lead back to the source
+	code.as_slice().parse().map_err(|e| LanguageError::Parse(match e {
+		UnmatchedOpen(bracket, position) => UnmatchedOpen(bracket, positions[position.offset]),
+		UnmatchedClose(bracket, position) => UnmatchedClose(bracket, positions[position.offset])
+	}))
+}
+
+// the program in the given language; None if it uses an extension the language doesn't have
+pub fn write(commands: &[Command], language: &Language) -> Option<String> {
+	if commands.iter().any(|command| command.uses_procedures() || contains_debug(command)) {
+		return None;
+	}
+
+	let words: Vec<&str> = instructions(commands).iter()
+		.map(|c| language.instructions[BRAINFUCK.iter().position(|b| b == c).unwrap()])
+		.collect();
+
+	Some(words.chunks(PER_LINE).map(|line| line.join(" ") + "\n").collect())
+}
+
+fn contains_debug(command: &Command) -> bool {
+	match *command {
+		Debug => true,
+		Loop(ref body) => body.iter().any(contains_debug),
+		_ => false
+	}
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use super::{by_name, parse, write, LanguageError, BLUB, LANGUAGES, OOK, PIKALANG};
+	use super::super::{Dialect, Interpreter, ParseBrainfuck, Position, Program, HELLOWORLD};
+	use super::super::Command::*;
+	use super::super::ParseError::*;
+	use super::super::config::TapeConfig;
+
+	#[test]
+	fn parsing() {
+		let source = b"Ook. Ook. Ook! Ook?\nOook! Ook!\tOok? Ook!   Ook! Ook.";
+		assert_eq!(parse(source, &OOK), Err(LanguageError::UnknownWord("Oook!".to_owned(),
+			Position { offset: 20, line: 2, column: 1 })));
+
+		let source = b"Ook. Ook. Ook! Ook?\nOok! Ook!\tOok? Ook!   Ook! Ook.";
+		assert_eq!(parse(source, &OOK), Ok(vec![IncrementByte, Loop(vec![DecrementByte]), OutputByte]));
+		assert_eq!(parse(b"pi pika ka chu pikachu pipi", &PIKALANG),
+			Ok(vec![IncrementByte, Loop(vec![DecrementByte]), OutputByte, IncrementPos]));
+		assert_eq!(parse(b"", &BLUB), Ok(vec![]));
+	}
+
+	#[test]
+	fn unmatched_brackets() {
+		// a word missing the one completing it isn't an instruction
+		assert_eq!(parse(b"Ook. Ook. Ook!", &OOK), Err(LanguageError::UnknownWord("Ook!".to_owned(),
+			Position { offset: 10, line: 1, column: 11 })));
+		// the positions are the source's rather than the translation's
+		assert_eq!(parse(b"pi\n  chu", &PIKALANG), Err(LanguageError::Parse(UnmatchedClose(']',
+			Position { offset: 5, line: 2, column: 3 }))));
+		assert_eq!(parse(b"Blub! Blub?", &BLUB), Err(LanguageError::Parse(UnmatchedOpen('[',
+			Position { offset: 0, line: 1, column: 1 }))));
+	}
+
+	#[test]
+	fn converting() {
+		let program = HELLOWORLD.parse().unwrap();
+
+		for &language in LANGUAGES {
+			let source = write(&program, language).unwrap();
+			assert_eq!(parse(source.as_bytes(), language), Ok(program.clone()), "{}", language.name);
+			assert_eq!(by_name(language.name), Some(language));
+
+			let mut interpreter = Interpreter::new(io::empty(), Vec::new(), TapeConfig::default());
+			interpreter.run(&Program::new(parse(source.as_bytes(), language).unwrap())).unwrap();
+			assert_eq!(interpreter.into_output(), b"Hello World!\n");
+		}
+
+		assert_eq!(write(&b"+[-]".as_ref().parse().unwrap(), &BLUB).unwrap(),
+			"Blub. Blub. Blub! Blub? Blub! Blub! Blub? Blub!\n");
+		let dump = b"+[#]".as_ref().parse_with(&Dialect { debug: true, ..Dialect::default() }).unwrap();
+		assert_eq!(write(&dump, &OOK), None);
+	}
+}
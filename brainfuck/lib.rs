@@ -0,0 +1,986 @@
+// struct literals spell out `field: value` even when the names match
+#![allow(clippy::redundant_field_names)]
+
+pub mod analysis;
+pub mod budget;
+pub mod config;
+pub mod debugger;
+pub mod format;
+#[cfg(any(test, feature = "fuzz"))]
+pub mod fuzz;
+pub mod generate;
+pub mod ir;
+pub mod languages;
+pub mod profiler;
+pub mod snapshot;
+pub mod trace;
+pub mod transpile;
+pub mod vm;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use budget::Budget;
+use config::{EofPolicy, Overflow, TapeConfig};
+use ir::Op;
+use vm::Bytecode;
+use self::Command::*;
+use self::ParseError::*;
+use self::RuntimeErrorKind::*;
+
+// pbrain procedures calling each other recurse on the native stack, so the nesting is limited
+const MAX_CALL_DEPTH: usize = 1024;
+
+/* tape */
+
+// the memory of a running program; everything but its I/O
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tape {
+	array: Vec<i64>,
+	pos: usize,
+	config: TapeConfig,
+	// the number of instructions executed so far, counting every loop condition check
+	steps: u64
+}
+
+impl Tape {
+	pub fn new(config: TapeConfig) -> Tape {
+		assert!(config.cells > 0, "error: the tape needs at least one cell");
+
+		Tape {
+			array: vec![0; config.cells],
+			pos: 0,
+			config: config,
+			steps: 0
+		}
+	}
+
+	pub fn cells(&self) -> &[i64] { &self.array }
+
+	pub fn pos(&self) -> usize { self.pos }
+
+	pub fn config(&self) -> &TapeConfig { &self.config }
+
+	pub fn steps(&self) -> u64 { self.steps }
+
+	#[inline]
+	pub fn cell(&self) -> i64 { self.array[self.pos] }
+
+	#[inline]
+	fn set_cell(&mut self, value: i64) { self.array[self.pos] = value }
+
+	#[inline]
+	fn add_at(&mut self, pos: usize, delta: i128) -> Result<(), RuntimeErrorKind> {
+		match self.config.add(self.array[pos], delta) {
+			Some(value) => {
+				self.array[pos] = value;
+				Ok(())
+			},
+			None => Err(CellOverflow)
+		}
+	}
+
+	#[inline]
+	fn add(&mut self, delta: i128) -> Result<(), RuntimeErrorKind> {
+		let pos = self.pos;
+		self.add_at(pos, delta)
+	}
+
+	// the position the given offset from the pointer refers to; grows the tape if needed
+	#[inline]
+	fn offset(&mut self, n: isize) -> Result<usize, RuntimeErrorKind> {
+		let len = self.array.len() as isize;
+		let target = self.pos as isize + n;
+
+		if target >= 0 && target < len { return Ok(target as usize); }
+
+		if target >= len && self.config.growable {
+			self.array.resize(target as usize + 1, 0);
+			Ok(target as usize)
+		} else if self.config.wrap_pointer {
+			Ok(target.rem_euclid(len) as usize)
+		} else if target < 0 {
+			Err(PointerUnderflow)
+		} else {
+			Err(PointerOverflow)
+		}
+	}
+
+	#[inline]
+	fn move_by(&mut self, n: isize) -> Result<(), RuntimeErrorKind> {
+		self.pos = self.offset(n)?;
+		Ok(())
+	}
+}
+
+/* interpreter */
+
+pub struct Interpreter<R, W> {
+	tape: Tape,
+	input: R,
+	output: W,
+	budget: Budget,
+	// the pbrain procedures defined so far by the value of the cell they were defined at,
+	// along with the instruction position of their '('
+	procedures: HashMap<i64, (usize, Rc<Vec<Command>>)>,
+	calls: usize,
+	// the number of bytes read from the input
+	consumed: u64
+}
+
+impl<R: Read, W: Write> Interpreter<R, W> {
+	pub fn new(input: R, output: W, config: TapeConfig) -> Interpreter<R, W> {
+		Interpreter {
+			tape: Tape::new(config),
+			input: input,
+			output: output,
+			budget: Budget::unlimited(),
+			procedures: HashMap::new(),
+			calls: 0,
+			consumed: 0
+		}
+	}
+
+	pub fn set_budget(&mut self, budget: Budget) { self.budget = budget }
+
+	pub fn tape(&self) -> &Tape { &self.tape }
+
+	pub fn output(&self) -> &W { &self.output }
+
+	pub fn into_output(self) -> W { self.output }
+
+	pub fn consumed(&self) -> u64 { self.consumed }
+
+	// runs the program compiled to bytecode, unless it uses procedures; further runs continue with
+	// the tape (and the procedures) left by the previous ones
+	pub fn run(&mut self, program: &Program) -> Result<(), RuntimeError> {
+		match program.bytecode(self.tape.config.overflow) {
+			Some(bytecode) => self.run_bytecode(bytecode),
+			None => self.run_unoptimized(program)
+		}
+	}
+
+	// runs the program as written, so that errors refer to positions in its source
+	pub fn run_unoptimized(&mut self, program: &Program) -> Result<(), RuntimeError> {
+		self.run_observed(program, &mut ())
+	}
+
+	pub fn run_observed<O: Observer>(&mut self, program: &Program, observer: &mut O) -> Result<(), RuntimeError> {
+		self.run_from(&program.commands, 0, observer).map(|_| ())
+	}
+
+	fn read_byte(&mut self) -> io::Result<Option<u8>> {
+		let mut buf = [0];
+
+		// make sure any prompt printed so far is visible before blocking on input
+		self.output.flush()?;
+
+		loop {
+			match self.input.read(&mut buf) {
+				Ok(0) => return Ok(None),
+				Ok(_) => {
+					self.consumed += 1;
+					return Ok(Some(buf[0]));
+				},
+				Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e)
+			}
+		}
+	}
+
+	// cells are output as raw bytes, so programs can produce any encoding (or none at all)
+	fn output_byte(&mut self) -> Result<(), RuntimeErrorKind> {
+		let byte = self.tape.cell() as u8;
+		self.output.write_all(&[byte]).map_err(|e| Output(e.kind()))
+	}
+
+	fn input_byte(&mut self) -> Result<(), RuntimeErrorKind> {
+		let byte = self.read_byte().map_err(|e| Input(e.kind()))?;
+		let tape = &mut self.tape;
+		match byte {
+			Some(byte) => tape.set_cell(byte as i64),
+			None => match tape.config.eof {
+				EofPolicy::Unchanged => {},
+				EofPolicy::Zero => tape.set_cell(0),
+				EofPolicy::MinusOne => {
+					let value = tape.config.width.wrap(-1);
+					tape.set_cell(value)
+				},
+				EofPolicy::Error => return Err(InputExhausted)
+			}
+		}
+		Ok(())
+	}
+
+	// counts an executed instruction against the budget
+	fn step(&mut self) -> Result<(), RuntimeErrorKind> {
+		self.tape.steps += 1;
+		self.budget.check(self.tape.steps)
+	}
+
+	fn error(&self, kind: RuntimeErrorKind, position: usize) -> RuntimeError {
+		RuntimeError {
+			kind: kind,
+			position: position,
+			steps: self.tape.steps
+		}
+	}
+
+	// runs commands starting at the given instruction position, returning the one following them
+	fn run_from<O: Observer>(&mut self, commands: &[Command], mut pc: usize, observer: &mut O)
+		-> Result<usize, RuntimeError>
+	{
+		for command in commands.iter() {
+			self.step().map_err(|kind| self.error(kind, pc))?;
+			observer.observe(&self.tape, pc).map_err(|kind| self.error(kind, pc))?;
+			match *command {
+				Loop(ref body) => pc = self.run_loop(body, pc, observer)?,
+				Procedure(ref body) => {
+					self.procedures.insert(self.tape.cell(), (pc, body.clone()));
+					pc += command.size() - 1;
+				},
+				Call => self.call(pc, observer)?,
+				ref command => self.execute(command).map_err(|kind| self.error(kind, pc))?
+			}
+			pc += 1;
+		}
+
+		Ok(pc)
+	}
+
+	// returns the instruction position of the loop's closing bracket
+	fn run_loop<O: Observer>(&mut self, body: &[Command], pc: usize, observer: &mut O) -> Result<usize, RuntimeError> {
+		let mut end = None;
+
+		while self.tape.cell() != 0 {
+			let close = self.run_from(body, pc + 1, observer)?;
+			self.step().map_err(|kind| self.error(kind, close))?;
+			observer.observe(&self.tape, close).map_err(|kind| self.error(kind, close))?;
+			end = Some(close);
+		}
+
+		Ok(end.unwrap_or_else(|| pc + 1 + body.iter().map(Command::size).sum::<usize>()))
+	}
+
+	fn call<O: Observer>(&mut self, pc: usize, observer: &mut O) -> Result<(), RuntimeError> {
+		let id = self.tape.cell();
+		let (start, body) = match self.procedures.get(&id) {
+			Some(&(start, ref body)) => (start, body.clone()),
+			None => return Err(self.error(UndefinedProcedure(id), pc))
+		};
+		if self.calls == MAX_CALL_DEPTH {
+			return Err(self.error(CallDepthExceeded, pc));
+		}
+
+		self.calls += 1;
+		let result = self.run_from(&body, start + 1, observer);
+		self.calls -= 1;
+
+		result.map(|_| ())
+	}
+
+	fn execute(&mut self, command: &Command) -> Result<(), RuntimeErrorKind> {
+		match *command {
+			IncrementPos => self.tape.move_by(1),
+			DecrementPos => self.tape.move_by(-1),
+			IncrementByte => self.tape.add(1),
+			DecrementByte => self.tape.add(-1),
+			OutputByte => self.output_byte(),
+			InputByte => self.input_byte(),
+			Debug => Ok(()),
+			Loop(..) | Procedure(..) | Call => unreachable!()
+		}
+	}
+}
+
+// gets to look at the tape before every instruction, including every check of a loop's condition
+pub trait Observer {
+	fn observe(&mut self, tape: &Tape, pc: usize) -> Result<(), RuntimeErrorKind>;
+}
+
+impl Observer for () {
+	fn observe(&mut self, _: &Tape, _: usize) -> Result<(), RuntimeErrorKind> { Ok(()) }
+}
+
+impl<O: Observer> Observer for &mut O {
+	fn observe(&mut self, tape: &Tape, pc: usize) -> Result<(), RuntimeErrorKind> { (**self).observe(tape, pc) }
+}
+
+impl<O: Observer> Observer for Option<O> {
+	fn observe(&mut self, tape: &Tape, pc: usize) -> Result<(), RuntimeErrorKind> {
+		match *self {
+			Some(ref mut observer) => observer.observe(tape, pc),
+			None => Ok(())
+		}
+	}
+}
+
+impl<A: Observer, B: Observer> Observer for (A, B) {
+	fn observe(&mut self, tape: &Tape, pc: usize) -> Result<(), RuntimeErrorKind> {
+		self.0.observe(tape, pc)?;
+		self.1.observe(tape, pc)
+	}
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RuntimeErrorKind {
+	PointerUnderflow,
+	PointerOverflow,
+	CellOverflow,
+	InputExhausted,
+	Input(io::ErrorKind),
+	Output(io::ErrorKind),
+	StepLimitExceeded,
+	TimedOut,
+	Cancelled,
+	// calling a pbrain procedure that wasn't defined for the current cell value
+	UndefinedProcedure(i64),
+	CallDepthExceeded
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RuntimeError {
+	pub kind: RuntimeErrorKind,
+	// the instruction being executed
+	pub position: usize,
+	// the number of instructions executed so far, counting every loop condition check
+	pub steps: u64
+}
+
+impl fmt::Display for RuntimeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.kind {
+			PointerUnderflow => write!(f, "the pointer moved left of the first cell"),
+			PointerOverflow => write!(f, "the pointer moved past the last cell"),
+			CellOverflow => write!(f, "the cell value overflowed"),
+			InputExhausted => write!(f, "the input ran out"),
+			Input(kind) => write!(f, "unable to read input ({:?})", kind),
+			Output(kind) => write!(f, "unable to write output ({:?})", kind),
+			StepLimitExceeded => write!(f, "the step limit was reached"),
+			TimedOut => write!(f, "the time budget ran out"),
+			Cancelled => write!(f, "execution was cancelled"),
+			UndefinedProcedure(id) => write!(f, "procedure {} is not defined", id),
+			CallDepthExceeded => write!(f, "procedure calls are nested too deeply")
+		}?;
+		write!(f, " at instruction {} after {} steps", self.position, self.steps)
+	}
+}
+
+impl RuntimeError {
+	// whether execution was stopped by its budget rather than by a fault in the program
+	pub fn aborted(&self) -> bool {
+		matches!(self.kind, StepLimitExceeded | TimedOut | Cancelled)
+	}
+}
+
+impl Error for RuntimeError {}
+
+/* commands */
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command {
+	IncrementPos,
+	DecrementPos,
+	IncrementByte,
+	DecrementByte,
+	OutputByte,
+	InputByte,
+	// '#', which dumps the tape when observed by a TapeDump and is a breakpoint for the debugger
+	Debug,
+	Loop(Vec<Command>),
+	// pbrain's '(...)', which defines a procedure identified by the current cell value
+	Procedure(Rc<Vec<Command>>),
+	// pbrain's ':', which calls the procedure identified by the current cell value
+	Call
+}
+
+impl Command {
+	// the number of instructions, counting both brackets of loops and procedures
+	pub fn size(&self) -> usize {
+		match *self {
+			Loop(ref body) => 2 + body.iter().map(Command::size).sum::<usize>(),
+			Procedure(ref body) => 2 + body.iter().map(Command::size).sum::<usize>(),
+			_ => 1
+		}
+	}
+
+	pub fn uses_procedures(&self) -> bool {
+		match *self {
+			Loop(ref body) => body.iter().any(Command::uses_procedures),
+			Procedure(..) | Call => true,
+			_ => false
+		}
+	}
+}
+
+// the program without any comments; instruction positions are indices into it
+pub fn instructions(commands: &[Command]) -> Vec<u8> {
+	let mut source = Vec::new();
+
+	for command in commands {
+		match *command {
+			IncrementPos => source.push(b'>'),
+			DecrementPos => source.push(b'<'),
+			IncrementByte => source.push(b'+'),
+			DecrementByte => source.push(b'-'),
+			OutputByte => source.push(b'.'),
+			InputByte => source.push(b','),
+			Debug => source.push(b'#'),
+			Loop(ref body) => {
+				source.push(b'[');
+				source.extend(instructions(body));
+				source.push(b']');
+			},
+			Procedure(ref body) => {
+				source.push(b'(');
+				source.extend(instructions(body));
+				source.push(b')');
+			},
+			Call => source.push(b':')
+		}
+	}
+
+	source
+}
+
+/* parsing */
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Position {
+	pub offset: usize,
+	pub line: usize,
+	pub column: usize
+}
+
+impl Position {
+	fn advance(&mut self, c: u8) {
+		if c == b'\n' {
+			self.line += 1;
+			self.column = 1;
+		} else if c & 0xC0 != 0x80 {
+			// don't count UTF-8 continuation bytes as separate columns
+			self.column += 1;
+		}
+	}
+}
+
+impl fmt::Display for Position {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "line {}, column {} (byte {})", self.line, self.column, self.offset)
+	}
+}
+
+// the brackets are either '[' and ']' or pbrain's '(' and ')'
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+	UnmatchedOpen(char, Position),
+	UnmatchedClose(char, Position)
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			UnmatchedOpen(bracket, position) => write!(f, "unmatched '{}' at {}", bracket, position),
+			UnmatchedClose(bracket, position) => write!(f, "unmatched '{}' at {}", bracket, position)
+		}
+	}
+}
+
+impl Error for ParseError {}
+
+// the optional extensions of the language recognized by the parser
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Dialect {
+	// '#' dumps the tape or pauses the debugger
+	pub debug: bool,
+	// pbrain's '(', ')' and ':'
+	pub procedures: bool,
+	// everything following the first '!' is the program's input rather than a comment
+	pub input_separator: bool
+}
+
+impl Dialect {
+	pub fn is_instruction(&self, c: u8) -> bool {
+		match c {
+			b'>' | b'<' | b'+' | b'-' | b'.' | b',' | b'[' | b']' => true,
+			b'#' => self.debug,
+			b'(' | b')' | b':' => self.procedures,
+			_ => false
+		}
+	}
+
+	// the program's source and the input following it, if any
+	pub fn split_input<'a>(&self, source: &'a [u8]) -> (&'a [u8], Option<&'a [u8]>) {
+		match source.iter().position(|&c| c == b'!') {
+			Some(separator) if self.input_separator => (&source[..separator], Some(&source[separator + 1..])),
+			_ => (source, None)
+		}
+	}
+}
+
+// where every instruction of the program is in its source, indexed by instruction position
+pub fn instruction_positions(source: &[u8], dialect: &Dialect) -> Vec<Position> {
+	let mut positions = Vec::new();
+	let mut position = Position { offset: 0, line: 1, column: 1 };
+
+	for (offset, &c) in dialect.split_input(source).0.iter().enumerate() {
+		position.offset = offset;
+		if dialect.is_instruction(c) { positions.push(position); }
+		position.advance(c);
+	}
+
+	positions
+}
+
+pub trait ParseBrainfuck {
+	fn parse_with(&self, dialect: &Dialect) -> Result<Vec<Command>, ParseError>;
+
+	fn parse(&self) -> Result<Vec<Command>, ParseError> {
+		self.parse_with(&Dialect::default())
+	}
+}
+
+impl ParseBrainfuck for &[u8] {
+	fn parse_with(&self, dialect: &Dialect) -> Result<Vec<Command>, ParseError> {
+		// the commands outside of every loop or procedure that is still open,
+		// along with the opening bracket and its position
+		let mut open: Vec<(u8, Position, Vec<Command>)> = Vec::new();
+		let mut commands = Vec::new();
+		let mut position = Position { offset: 0, line: 1, column: 1 };
+
+		for (offset, &c) in dialect.split_input(self).0.iter().enumerate() {
+			position.offset = offset;
+
+			match c {
+				b'>' => commands.push(IncrementPos),
+				b'<' => commands.push(DecrementPos),
+				b'+' => commands.push(IncrementByte),
+				b'-' => commands.push(DecrementByte),
+				b'.' => commands.push(OutputByte),
+				b',' => commands.push(InputByte),
+				b'#' if dialect.debug => commands.push(Debug),
+				b':' if dialect.procedures => commands.push(Call),
+				b'[' => open.push((c, position, mem::take(&mut commands))),
+				b'(' if dialect.procedures => open.push((c, position, mem::take(&mut commands))),
+				b']' => match open.pop() {
+					Some((b'[', _, outer)) => {
+						let body = mem::replace(&mut commands, outer);
+						commands.push(Loop(body));
+					},
+					_ => return Err(UnmatchedClose(']', position))
+				},
+				b')' if dialect.procedures => match open.pop() {
+					Some((b'(', _, outer)) => {
+						let body = mem::replace(&mut commands, outer);
+						commands.push(Procedure(Rc::new(body)));
+					},
+					_ => return Err(UnmatchedClose(')', position))
+				},
+				_ => {}
+			}
+
+			position.advance(c);
+		}
+
+		match open.into_iter().next() {
+			Some((bracket, position, _)) => Err(UnmatchedOpen(bracket as char, position)),
+			None => Ok(commands)
+		}
+	}
+}
+
+/* programs */
+
+// a parsed program, ready to be run any number of times
+#[derive(Debug, Clone)]
+pub struct Program {
+	commands: Vec<Command>,
+	// programs using procedures can't be optimized
+	optimizable: bool,
+	// the commands optimized for each kind of overflow, as loops only fold the same on some kinds of
+	// cells; each is only done once, by the first run needing it
+	ops: [OnceLock<Vec<Op>>; 3],
+	bytecode: [OnceLock<Bytecode>; 3],
+	// the input following a '!' in the source
+	input: Option<Vec<u8>>
+}
+
+impl Program {
+	pub fn new(commands: Vec<Command>) -> Program {
+		Program {
+			optimizable: !commands.iter().any(Command::uses_procedures),
+			commands: commands,
+			ops: Default::default(),
+			bytecode: Default::default(),
+			input: None
+		}
+	}
+
+	pub fn parse(source: &[u8]) -> Result<Program, ParseError> {
+		Program::parse_with(source, &Dialect::default())
+	}
+
+	pub fn parse_with(source: &[u8], dialect: &Dialect) -> Result<Program, ParseError> {
+		let mut program = Program::new(source.parse_with(dialect)?);
+		program.input = dialect.split_input(source).1.map(|input| input.to_vec());
+
+		Ok(program)
+	}
+
+	pub fn commands(&self) -> &[Command] { &self.commands }
+
+	pub fn optimizable(&self) -> bool { self.optimizable }
+
+	pub fn ops(&self, overflow: Overflow) -> Option<&[Op]> {
+		if !self.optimizable { return None; }
+		Some(self.ops[overflow as usize].get_or_init(|| ir::optimize(&self.commands, overflow)))
+	}
+
+	pub fn bytecode(&self, overflow: Overflow) -> Option<&Bytecode> {
+		if !self.optimizable { return None; }
+		// the instructions of a tree of commands always have matching brackets
+		Some(self.bytecode[overflow as usize].get_or_init(|| Bytecode::compile(&instructions(&self.commands), overflow).unwrap()))
+	}
+
+	pub fn input(&self) -> Option<&[u8]> { self.input.as_ref().map(|input| &input[..]) }
+}
+
+// programs are the same whatever they have been optimized for so far
+impl PartialEq for Program {
+	fn eq(&self, other: &Program) -> bool {
+		self.commands == other.commands && self.input == other.input
+	}
+}
+
+impl FromStr for Program {
+	type Err = ParseError;
+
+	fn from_str(source: &str) -> Result<Program, ParseError> {
+		Program::parse(source.as_bytes())
+	}
+}
+
+pub const HELLOWORLD: &[u8] = include_bytes!("programs/helloworld.b");
+pub const FIZZBUZZ: &[u8] = include_bytes!("programs/fizzbuzz.b");
+pub const MANDELBROT: &[u8] = include_bytes!("programs/mandelbrot.b");
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use std::io::{self, Cursor};
+	use std::ptr;
+	use std::rc::Rc;
+	use super::{ir, Dialect, Interpreter, ParseBrainfuck, Position, Program, RuntimeError, RuntimeErrorKind, Tape, FIZZBUZZ, HELLOWORLD};
+	use super::RuntimeErrorKind::*;
+	use super::budget::Budget;
+	use super::config::{CellWidth, EofPolicy, Overflow, TapeConfig};
+	use super::Command::*;
+	use super::ParseError::*;
+
+	fn run_with_input(program: &[u8], input: &[u8], eof: EofPolicy) -> Tape {
+		let config = TapeConfig { eof: eof, ..TapeConfig::default() };
+		let mut interpreter = Interpreter::new(Cursor::new(input.to_vec()), io::sink(), config);
+		interpreter.run_unoptimized(&Program::parse(program).unwrap()).unwrap();
+		interpreter.tape
+	}
+
+	#[test]
+	fn reading_input() {
+		let tape = run_with_input(b",>,>,", b"ab", EofPolicy::Zero);
+
+		assert_eq!(&tape.array[..3], &[97, 98, 0]);
+	}
+
+	#[test]
+	fn eof_policies() {
+		let program = b"+++++,";
+
+		assert_eq!(run_with_input(program, b"", EofPolicy::Unchanged).array[0], 5);
+		assert_eq!(run_with_input(program, b"", EofPolicy::Zero).array[0], 0);
+		assert_eq!(run_with_input(program, b"", EofPolicy::MinusOne).array[0], -1);
+	}
+
+	#[test]
+	fn counting_input_bytes() {
+		// wc -c: read until EOF, counting bytes in the second cell
+		let tape = run_with_input(b",[>+<,]", b"hello, world\n", EofPolicy::Zero);
+
+		assert_eq!(tape.array[1], 13);
+	}
+
+	#[test]
+	fn counting_input_bytes_with_minus_one_eof() {
+		let tape = run_with_input(b",+[>+<,+]", b"abc", EofPolicy::MinusOne);
+
+		assert_eq!(tape.array[1], 3);
+	}
+
+	#[test]
+	fn parsing_nested_loops() {
+		let program = b"+[->[-]<]".as_ref().parse();
+
+		assert_eq!(program, Ok(vec![
+			IncrementByte,
+			Loop(vec![DecrementByte, IncrementPos, Loop(vec![DecrementByte]), DecrementPos])
+		]));
+	}
+
+	#[test]
+	fn parsing_ignores_comments() {
+		assert_eq!(b"a + b. [c]".as_ref().parse(), Ok(vec![IncrementByte, OutputByte, Loop(vec![])]));
+	}
+
+	#[test]
+	fn unmatched_close() {
+		let error = b"+[-]]>".as_ref().parse();
+
+		assert_eq!(error, Err(UnmatchedClose(']', Position { offset: 4, line: 1, column: 5 })));
+	}
+
+	#[test]
+	fn unmatched_open() {
+		let error = b"comment\n+[[-]\n  >".as_ref().parse();
+
+		assert_eq!(error, Err(UnmatchedOpen('[', Position { offset: 9, line: 2, column: 2 })));
+	}
+
+	#[test]
+	fn positions_count_characters() {
+		let error = "żółw ]".as_bytes().parse();
+
+		assert_eq!(error, Err(UnmatchedClose(']', Position { offset: 8, line: 1, column: 6 })));
+	}
+
+	fn try_run(program: &[u8], config: TapeConfig) -> (Tape, Result<(), RuntimeError>) {
+		let mut interpreter = Interpreter::new(io::empty(), io::sink(), config);
+		let result = interpreter.run_unoptimized(&Program::parse(program).unwrap());
+		(interpreter.tape, result)
+	}
+
+	fn run_with_config(program: &[u8], config: TapeConfig) -> Tape {
+		let (tape, result) = try_run(program, config);
+		result.unwrap();
+		tape
+	}
+
+	fn dialect(width: CellWidth, overflow: Overflow) -> TapeConfig {
+		TapeConfig { width: width, overflow: overflow, ..TapeConfig::default() }
+	}
+
+	#[test]
+	fn cell_widths() {
+		assert_eq!(run_with_config(b"-", dialect(CellWidth::U8, Overflow::Wrapping)).array[0], 255);
+		assert_eq!(run_with_config(b"-", dialect(CellWidth::U16, Overflow::Wrapping)).array[0], 65535);
+		assert_eq!(run_with_config(b"-", dialect(CellWidth::U32, Overflow::Wrapping)).array[0], 4294967295);
+		assert_eq!(run_with_config(b"-", dialect(CellWidth::I64, Overflow::Wrapping)).array[0], -1);
+		// a loop that only terminates with 8-bit wrapping cells
+		assert_eq!(run_with_config(b"+[+]>+", TapeConfig::classic()).array[..2], [0, 1]);
+	}
+
+	#[test]
+	fn saturating_cells() {
+		assert_eq!(run_with_config(b"--+", dialect(CellWidth::U8, Overflow::Saturating)).array[0], 1);
+	}
+
+	#[test]
+	fn checked_cells() {
+		let (tape, result) = try_run(b"+>-", dialect(CellWidth::U16, Overflow::Checked));
+
+		assert_eq!(result, Err(RuntimeError { kind: CellOverflow, position: 2, steps: 3 }));
+		assert_eq!(tape.array[..2], [1, 0]);
+	}
+
+	#[test]
+	fn minus_one_eof_in_unsigned_cells() {
+		let config = TapeConfig { eof: EofPolicy::MinusOne, ..TapeConfig::classic() };
+
+		assert_eq!(run_with_config(b",", config).array[0], 255);
+	}
+
+	#[test]
+	fn growable_tape() {
+		let config = TapeConfig { cells: 2, growable: true, ..TapeConfig::default() };
+		let tape = run_with_config(b">>>>+", config);
+
+		assert_eq!(tape.array, vec![0, 0, 0, 0, 1]);
+		assert_eq!(tape.pos, 4);
+	}
+
+	#[test]
+	fn leaving_a_fixed_tape() {
+		let config = TapeConfig { cells: 3, ..TapeConfig::default() };
+
+		assert_eq!(try_run(b">>>", config).1, Err(RuntimeError { kind: PointerOverflow, position: 2, steps: 3 }));
+		assert_eq!(try_run(b"+[<]", config).1, Err(RuntimeError { kind: PointerUnderflow, position: 2, steps: 3 }));
+	}
+
+	#[test]
+	fn error_positions_in_loops() {
+		// the first loop is skipped, the second one leaves the tape on its third iteration
+		let config = TapeConfig { cells: 3, ..TapeConfig::default() };
+		let result = try_run(b"[>>]+++[>-]", config).1;
+
+		assert_eq!(result, Err(RuntimeError { kind: PointerOverflow, position: 8, steps: 12 }));
+	}
+
+	#[test]
+	fn running_out_of_input() {
+		let config = TapeConfig { eof: EofPolicy::Error, ..TapeConfig::default() };
+		let mut interpreter = Interpreter::new(&b"a"[..], io::sink(), config);
+		let result = interpreter.run_unoptimized(&Program::parse(b",>,").unwrap());
+
+		assert_eq!(result, Err(RuntimeError { kind: InputExhausted, position: 2, steps: 3 }));
+	}
+
+	#[test]
+	fn wrapping_pointer() {
+		let config = TapeConfig { cells: 3, wrap_pointer: true, ..TapeConfig::default() };
+
+		assert_eq!(run_with_config(b"<+>>++", config).array, vec![0, 2, 1]);
+	}
+
+	#[test]
+	fn capturing_output() {
+		let mut interpreter = Interpreter::new(io::empty(), Vec::new(), TapeConfig::classic());
+		interpreter.run(&Program::parse(HELLOWORLD).unwrap()).unwrap();
+
+		assert_eq!(interpreter.into_output(), b"Hello World!\n");
+	}
+
+	#[test]
+	fn running_a_program_many_times() {
+		let program: Program = ",[.,]".parse().unwrap();
+		let config = TapeConfig { eof: EofPolicy::Zero, ..TapeConfig::classic() };
+
+		for input in &["first", "second"] {
+			let mut interpreter = Interpreter::new(input.as_bytes(), Vec::new(), config);
+			interpreter.run(&program).unwrap();
+			assert_eq!(interpreter.output(), input.as_bytes());
+		}
+	}
+
+	#[test]
+	fn optimized_and_unoptimized_runs() {
+		let program = Program::parse(FIZZBUZZ).unwrap();
+		let mut optimized = Interpreter::new(io::empty(), Vec::new(), TapeConfig::classic());
+		let mut unoptimized = Interpreter::new(io::empty(), Vec::new(), TapeConfig::classic());
+		optimized.run(&program).unwrap();
+		unoptimized.run_unoptimized(&program).unwrap();
+
+		assert!(optimized.output().starts_with(b"1\n2\nFizz\n4\nBuzz\n"));
+		assert_eq!(optimized.output(), unoptimized.output());
+		assert_eq!(optimized.tape().cells(), unoptimized.tape().cells());
+	}
+
+	// the output of the tree walker, the optimized ops and the bytecode, in that order
+	fn run_engines(source: &[u8], overflow: Overflow) -> Vec<Result<Vec<u8>, RuntimeErrorKind>> {
+		let program = Program::parse(source).unwrap();
+		let config = TapeConfig { overflow: overflow, ..TapeConfig::classic() };
+		let mut interpreters: Vec<_> = (0..3).map(|_| {
+			let mut interpreter = Interpreter::new(io::empty(), Vec::new(), config);
+			interpreter.set_budget(Budget { max_steps: Some(100_000), ..Budget::unlimited() });
+			interpreter
+		}).collect();
+
+		let results = [
+			interpreters[0].run_unoptimized(&program),
+			interpreters[1].run_ir(program.ops(overflow).unwrap()),
+			interpreters[2].run(&program)
+		];
+		results.iter().zip(interpreters).map(|(result, interpreter)| match *result {
+			Ok(()) => Ok(interpreter.into_output()),
+			Err(ref e) => Err(e.kind)
+		}).collect()
+	}
+
+	#[test]
+	fn engines_on_overflow_modes() {
+		let saturated = [&b">"[..], &[b'+'; 250][..], &b"<+++[->++++>+<--<]>."[..]].concat();
+		assert_eq!(run_engines(&saturated, Overflow::Saturating), vec![Ok(vec![253]); 3]);
+		assert_eq!(run_engines(b"+[+]", Overflow::Checked), vec![Err(CellOverflow); 3]);
+		assert_eq!(run_engines(b"+[+]", Overflow::Saturating), vec![Err(StepLimitExceeded); 3]);
+	}
+
+	#[test]
+	fn optimizing_for_each_overflow() {
+		// the additions only cancel out on wrapping cells
+		let program = Program::parse(b"+-.").unwrap();
+		assert_eq!(program.ops(Overflow::Wrapping).unwrap(), &[ir::Op::Output][..]);
+		assert_eq!(program.ops(Overflow::Checked).unwrap(), &ir::optimize(program.commands(), Overflow::Checked)[..]);
+		assert!(ptr::eq(program.bytecode(Overflow::Checked).unwrap(), program.bytecode(Overflow::Checked).unwrap()));
+		assert_ne!(program.bytecode(Overflow::Wrapping), program.bytecode(Overflow::Checked));
+		assert_eq!(program, Program::parse(b"+-.").unwrap());
+	}
+
+	fn pbrain() -> Dialect {
+		Dialect { procedures: true, ..Dialect::default() }
+	}
+
+	#[test]
+	fn parsing_extensions() {
+		assert_eq!(b"+#:".as_ref().parse(), Ok(vec![IncrementByte]));
+		assert_eq!(b"+#".as_ref().parse_with(&Dialect { debug: true, ..Dialect::default() }), Ok(vec![IncrementByte, Debug]));
+		assert_eq!(b"(+[-]):".as_ref().parse_with(&pbrain()), Ok(vec![
+			Procedure(Rc::new(vec![IncrementByte, Loop(vec![DecrementByte])])),
+			Call
+		]));
+	}
+
+	#[test]
+	fn mismatched_brackets() {
+		assert_eq!(b"([)]".as_ref().parse_with(&pbrain()), Err(UnmatchedClose(')', Position { offset: 2, line: 1, column: 3 })));
+		assert_eq!(b"+(".as_ref().parse_with(&pbrain()), Err(UnmatchedOpen('(', Position { offset: 1, line: 1, column: 2 })));
+	}
+
+	#[test]
+	fn calling_procedures() {
+		// procedure 0 adds two to the current cell, procedure 1 prints it
+		let program = Program::parse_with(b"(++)>+(.)<:>:", &pbrain()).unwrap();
+		let mut interpreter = Interpreter::new(io::empty(), Vec::new(), TapeConfig::default());
+		interpreter.run(&program).unwrap();
+
+		assert!(!program.optimizable());
+		assert!(program.ops(Overflow::Wrapping).is_none());
+		assert_eq!(interpreter.output(), &[1]);
+		assert_eq!(interpreter.tape.array[..2], [2, 1]);
+	}
+
+	#[test]
+	fn recursive_procedures() {
+		// procedure 1 counts up in the next cell, printing it and calling itself until it reaches 3
+		let program = Program::parse_with(b"+(>+.---[+++<:>]<):", &pbrain()).unwrap();
+		let mut interpreter = Interpreter::new(io::empty(), Vec::new(), TapeConfig::default());
+
+		assert_eq!(interpreter.run(&program), Ok(()));
+		assert_eq!(interpreter.output(), &[1, 2, 3]);
+	}
+
+	#[test]
+	fn procedure_errors() {
+		let mut interpreter = Interpreter::new(io::empty(), io::sink(), TapeConfig::default());
+		let undefined = interpreter.run(&Program::parse_with(b"+(-)++:", &pbrain()).unwrap());
+
+		assert_eq!(undefined, Err(RuntimeError { kind: UndefinedProcedure(3), position: 6, steps: 5 }));
+
+		let mut interpreter = Interpreter::new(io::empty(), io::sink(), TapeConfig::default());
+		let endless = interpreter.run(&Program::parse_with(b"(:):", &pbrain()).unwrap());
+
+		assert_eq!(endless.unwrap_err().kind, CallDepthExceeded);
+	}
+
+	#[test]
+	fn embedded_input() {
+		let dialect = Dialect { input_separator: true, ..Dialect::default() };
+		let program = Program::parse_with(b",[.,]!ab!c", &dialect).unwrap();
+
+		assert_eq!(program.commands(), &[InputByte, Loop(vec![OutputByte, InputByte])]);
+		assert_eq!(program.input(), Some(&b"ab!c"[..]));
+		assert_eq!(Program::parse(b",[.,]!ab").unwrap().input(), None);
+	}
+}
@@ -1,92 +1,240 @@
-use self::Command::*;
+#![allow(clippy::redundant_field_names)]
 
-struct Tape {
-	array: [isize; 30000],
-	pos: usize
+mod cli;
+
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Write};
+use std::process;
+use std::time::{Duration, Instant};
+use brainfuck::{analysis, instruction_positions, instructions, languages, transpile, Interpreter, Program, RuntimeError, Tape};
+use brainfuck::debugger::{Debugger, TapeDump};
+use brainfuck::format::{self, Style};
+use brainfuck::generate;
+use brainfuck::profiler::Profiler;
+use brainfuck::snapshot::{Recorder, Snapshot};
+use brainfuck::trace::Tracer;
+use brainfuck::vm::Bytecode;
+use cli::{Engine, Options, Source, Syntax};
+
+/* running */
+
+const EXIT_IO: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_PARSE: i32 = 3;
+const EXIT_RUNTIME: i32 = 4;
+const EXIT_ABORTED: i32 = 5;
+const EXIT_LINT: i32 = 6;
+
+// the number of loops listed by --profile
+const PROFILE_LOOPS: usize = 10;
+
+fn fail<T: fmt::Display, R>(code: i32, message: T) -> R {
+	let _ = io::stdout().flush();
+	eprintln!("error: {}", message);
+	process::exit(code)
 }
 
-impl Tape {
-	fn new() -> Tape {
-		Tape {
-			array: [0; 30000],
-			pos: 0
+fn main() {
+	let options = match cli::parse_args(env::args().skip(1)) {
+		Ok(Some(options)) => options,
+		Ok(None) => return println!("{}", cli::USAGE),
+		Err(e) => fail(EXIT_USAGE, format!("{}\n\n{}", e, cli::USAGE))
+	};
+
+	let source = match options.source {
+		Source::File(ref path) => fs::read(path)
+			.unwrap_or_else(|e| fail(EXIT_IO, format!("unable to read {}: {}", path, e))),
+		Source::Inline(ref code) => code.clone().into_bytes()
+	};
+	let dialect = options.dialect;
+	// the other languages are run and converted as the brainfuck they spell
+	let source = match options.language {
+		Syntax::Brainfuck => source,
+		Syntax::Substitution(language) => {
+			instructions(&languages::parse(&source, language).unwrap_or_else(|e| fail(EXIT_PARSE, e)))
 		}
+	};
+
+	if options.generate {
+		return println!("{}", generate::shortest(&source));
+	}
+
+	if options.format {
+		let formatted = format::format_source(&source, &dialect, &Style::default());
+		return print!("{}", formatted.unwrap_or_else(|e| fail(EXIT_PARSE, e)));
 	}
 
-	fn run(&mut self, commands: &[Command]) {
-		for command in commands.iter() {
-			match *command {
-				IncrementPos => self.pos += 1,
-				DecrementPos => self.pos -= 1,
-				IncrementByte => self.array[self.pos] += 1,
-				DecrementByte => self.array[self.pos] -= 1,
-				OutputByte => print!("{}", self.array[self.pos] as u8 as char),
-				InputByte => unimplemented!(),
-				Loop(ref command_loop) => while self.array[self.pos] != 0 {
-					self.run(&command_loop)
+	// plain programs are compiled to bytecode straight from their source, as building a tree of
+	// their loops first would limit how deeply they can be nested
+	let compiled = options.engine == Engine::Bytecode && options.convert.is_none() && !options.minify && !options.lint
+		&& options.emit.is_none() && !options.profile && options.trace.is_none() && !dialect.debug && !dialect.procedures;
+	if compiled {
+		let (code, embedded_input) = dialect.split_input(&source);
+		let bytecode = Bytecode::compile(code, options.config.overflow).unwrap_or_else(|e| fail(EXIT_PARSE, e));
+		let snapshot = options.resume.as_ref().map(|path| read_snapshot(path, &bytecode, options.config.cells));
+
+		// the output is only kept when it may have to be saved with the state
+		return match options.checkpoint {
+			Some(ref path) => {
+				let mut interpreter = interpreter(&options, embedded_input, Recorder::new(io::stdout()));
+				let (result, elapsed) = run_compiled(&mut interpreter, &bytecode, snapshot.as_ref());
+
+				if let Err(ref e) = result {
+					if let Some(snapshot) = interpreter.snapshot(&bytecode, e, interpreter.output().recorded()) {
+						fs::write(path, snapshot.to_string())
+							.unwrap_or_else(|e| fail(EXIT_IO, format!("unable to write {}: {}", path, e)));
+						eprintln!("saved the state to {}", path);
+					}
 				}
+				finish(&options, interpreter.tape(), elapsed, result, None)
+			},
+			None => {
+				let mut interpreter = interpreter(&options, embedded_input, io::stdout());
+				let (result, elapsed) = run_compiled(&mut interpreter, &bytecode, snapshot.as_ref());
+				finish(&options, interpreter.tape(), elapsed, result, None)
 			}
+		};
+	} else if options.checkpoint.is_some() || options.resume.is_some() {
+		fail(EXIT_USAGE, "only plain programs run by the bytecode engine can be saved and resumed")
+	}
+
+	let program = Program::parse_with(&source, &dialect).unwrap_or_else(|e| fail(EXIT_PARSE, e));
+
+	match options.convert {
+		Some(Syntax::Brainfuck) => return println!("{}", String::from_utf8_lossy(&instructions(program.commands()))),
+		Some(Syntax::Substitution(language)) => return print!("{}", languages::write(program.commands(), language)
+			.unwrap_or_else(|| fail(EXIT_USAGE, format!("{} has no procedures or '#'", language.name)))),
+		None => {}
+	}
+
+	if options.minify {
+		let minified = format::minify_source(&source, &dialect, &options.config).unwrap_or_else(|e| fail(EXIT_PARSE, e));
+		// a newline after the input would become part of it
+		return match program.input() {
+			Some(_) => print!("{}", minified),
+			None => println!("{}", minified)
+		};
+	}
+
+	if options.lint {
+		let diagnostics = analysis::analyze(program.commands(), &options.config);
+		print!("{}", analysis::report(&diagnostics, &instruction_positions(&source, &dialect)));
+		if !diagnostics.is_empty() {
+			fail(EXIT_LINT, format!("found {} problem(s)", diagnostics.len()))
 		}
+		return;
 	}
-}
 
-enum Command {
-	IncrementPos,
-	DecrementPos,
-	IncrementByte,
-	DecrementByte,
-	OutputByte,
-	InputByte,
-	Loop(Vec<Command>)
+	if let Some(target) = options.emit {
+		let ops = program.ops(options.config.overflow)
+			.unwrap_or_else(|| fail(EXIT_USAGE, "programs using procedures can't be transpiled"));
+		return print!("{}", transpile::transpile(ops, &options.config, target));
+	}
+
+	let mut interpreter = interpreter(&options, program.input(), io::stdout());
+
+	let mut profiler = if options.profile { Some(Profiler::new(program.commands(), options.config.overflow)) } else { None };
+	let debugger = if options.debug {
+		// the program may be reading stdin, so the commands come from the terminal if there is one
+		let commands: Box<dyn BufRead> = match File::open("/dev/tty") {
+			Ok(tty) => Box::new(BufReader::new(tty)),
+			Err(_) => Box::new(BufReader::new(io::stdin()))
+		};
+		Some(Debugger::new(program.commands(), commands, io::stderr()))
+	} else {
+		None
+	};
+	// when debugging, '#' pauses the program instead
+	let dump = if dialect.debug && !options.debug { Some(TapeDump::new(program.commands(), io::stderr())) } else { None };
+	let tracer = options.trace.map(|format| {
+		let output: Box<dyn Write> = match options.trace_file {
+			Some(ref path) => match File::create(path) {
+				Ok(file) => Box::new(BufWriter::new(file)),
+				Err(e) => fail(EXIT_IO, format!("unable to create {}: {}", path, e))
+			},
+			None => Box::new(io::stderr())
+		};
+		Tracer::new(program.commands(), format, output)
+	});
+
+	let start = Instant::now();
+	let result = if profiler.is_some() || debugger.is_some() || dump.is_some() || tracer.is_some() {
+		// observers see the program as written, so it isn't optimized
+		interpreter.run_observed(&program, &mut (&mut profiler, (debugger, (dump, tracer))))
+	} else {
+		match options.engine {
+			Engine::Bytecode => interpreter.run(&program),
+			Engine::Ir => match program.ops(options.config.overflow) {
+				Some(ops) => interpreter.run_ir(ops),
+				None => interpreter.run_unoptimized(&program)
+			},
+			_ => interpreter.run_unoptimized(&program)
+		}
+	};
+	let elapsed = start.elapsed();
+
+	let report = profiler.map(|profiler| {
+		profiler.report(program.commands(), &instruction_positions(&source, &dialect), PROFILE_LOOPS)
+	});
+	finish(&options, interpreter.tape(), elapsed, result, report)
 }
 
-trait ParseBrainfuck {
-	fn parse(&self) -> Vec<Command>;
-	fn _parse(&self) -> (Vec<Command>, u32);
+// an input file takes precedence over the input following a '!'
+fn interpreter<W: Write>(options: &Options, embedded_input: Option<&[u8]>, output: W) -> Interpreter<Box<dyn Read>, W> {
+	let input: Box<dyn Read> = match (&options.input, embedded_input) {
+		(Some(path), _) => match File::open(path) {
+			Ok(file) => Box::new(BufReader::new(file)),
+			Err(e) => fail(EXIT_IO, format!("unable to open {}: {}", path, e))
+		},
+		(None, Some(input)) => Box::new(Cursor::new(input.to_vec())),
+		(None, None) => Box::new(io::stdin())
+	};
+	let mut interpreter = Interpreter::new(input, output, options.config);
+	let mut budget = options.budget.clone();
+	// a timeout too far in the future to be represented is no deadline at all
+	budget.deadline = options.timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+	interpreter.set_budget(budget);
+
+	interpreter
 }
 
-impl<'a> ParseBrainfuck for &'a [u8] {
-	fn parse(&self) -> Vec<Command> {
-		let sanitized: Vec<u8> = (**self).iter().filter(|&c| b"><+-.,[]".contains(c)).map(|&c| c).collect();
-		(&*sanitized)._parse().0
+fn read_snapshot(path: &str, bytecode: &Bytecode, max_cells: usize) -> Snapshot {
+	let text = fs::read_to_string(path).unwrap_or_else(|e| fail(EXIT_IO, format!("unable to read {}: {}", path, e)));
+	let snapshot = Snapshot::parse(&text, max_cells).unwrap_or_else(|e| fail(EXIT_PARSE, format!("{}: {}", path, e)));
+	if !snapshot.matches(bytecode) {
+		fail(EXIT_USAGE, format!("{} is the state of a different program", path))
 	}
 
-	fn _parse(&self) -> (Vec<Command>, u32) {
-		let mut commands = Vec::new();
-		let len = self.len();
-		let mut index = 0;
-
-		while index < len {
-			match self[index] {
-				b'>' => commands.push(IncrementPos),
-				b'<' => commands.push(DecrementPos),
-				b'+' => commands.push(IncrementByte),
-				b'-' => commands.push(DecrementByte),
-				b'.' => commands.push(OutputByte),
-				b',' => commands.push(InputByte),
-				b'[' => {
-					index += 1;
-					let (parsed, count) = (&self[index..])._parse();
-					commands.push(Loop(parsed));
-					index += count as usize;
-				},
-				b']' => break,
-				_ => unreachable!()
-			}
-			index += 1;
-		}
+	snapshot
+}
 
-		(commands, index as u32)
-	}
+// runs the bytecode from the start, or from where the snapshot was taken
+fn run_compiled<W: Write>(interpreter: &mut Interpreter<Box<dyn Read>, W>, bytecode: &Bytecode, snapshot: Option<&Snapshot>)
+	-> (Result<(), RuntimeError>, Duration)
+{
+	let start = Instant::now();
+	let result = match snapshot {
+		Some(snapshot) => interpreter.resume(bytecode, snapshot),
+		None => interpreter.run_bytecode(bytecode)
+	};
+
+	(result, start.elapsed())
 }
 
-fn main() {
-	let mut tape = Tape::new();
-//	let helloworld = b"++++++++++[>+++++++>++++++++++>+++>+<<<<-]>++.>+.+++++++..+++.>++.<<+++++++++++++++.>.+++.------.--------.>+.>.";
-//	let mandelbrot = b"+++++++++++++[->++>>>+++++>++>+<<<<<<]>>>>>++++++>--->>>>>>>>>>+++++++++++++++[[>>>>>>>>>]+[<<<<<<<<<]>>>>>>>>>-]+[>>>>>>>>[-]>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>[-]+<<<<<<<+++++[-[->>>>>>>>>+<<<<<<<<<]>>>>>>>>>]>>>>>>>+>>>>>>>>>>>>>>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<<<[<<<<<<<<<]>>>[-]+[>>>>>>[>>>>>>>[-]>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>[-]+<<<<<<++++[-[->>>>>>>>>+<<<<<<<<<]>>>>>>>>>]>>>>>>+<<<<<<+++++++[-[->>>>>>>>>+<<<<<<<<<]>>>>>>>>>]>>>>>>+<<<<<<<<<<<<<<<<[<<<<<<<<<]>>>[[-]>>>>>>[>>>>>>>[-<<<<<<+>>>>>>]<<<<<<[->>>>>>+<<+<<<+<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<<[->>>>>>>+<<+<<<+<<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<<[->>>>>>>+<<+<<<<<]>>>>>>>>>+++++++++++++++[[>>>>>>>>>]+>[-]>[-]>[-]>[-]>[-]>[-]>[-]>[-]>[-]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>-]+[>+>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>->>>>[-<<<<+>>>>]<<<<[->>>>+<<<<<[->>[-<<+>>]<<[->>+>>+<<<<]+>>>>>>>>>]<<<<<<<<[<<<<<<<<<]]>>>>>>>>>[>>>>>>>>>]<<<<<<<<<[>[->>>>>>>>>+<<<<<<<<<]<<<<<<<<<<]>[->>>>>>>>>+<<<<<<<<<]<+>>>>>>>>]<<<<<<<<<[>[-]<->>>>[-<<<<+>[<->-<<<<<<+>>>>>>]<[->+<]>>>>]<<<[->>>+<<<]<+<<<<<<<<<]>>>>>>>>>[>+>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>->>>>>[-<<<<<+>>>>>]<<<<<[->>>>>+<<<<<<[->>>[-<<<+>>>]<<<[->>>+>+<<<<]+>>>>>>>>>]<<<<<<<<[<<<<<<<<<]]>>>>>>>>>[>>>>>>>>>]<<<<<<<<<[>>[->>>>>>>>>+<<<<<<<<<]<<<<<<<<<<<]>>[->>>>>>>>>+<<<<<<<<<]<<+>>>>>>>>]<<<<<<<<<[>[-]<->>>>[-<<<<+>[<->-<<<<<<+>>>>>>]<[->+<]>>>>]<<<[->>>+<<<]<+<<<<<<<<<]>>>>>>>>>[>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>+++++++++++++++[[>>>>>>>>>]<<<<<<<<<-<<<<<<<<<[<<<<<<<<<]>>>>>>>>>-]+>>>>>>>>>>>>>>>>>>>>>+<<<[<<<<<<<<<]>>>>>>>>>[>>>[-<<<->>>]+<<<[->>>->[-<<<<+>>>>]<<<<[->>>>+<<<<<<<<<<<<<[<<<<<<<<<]>>>>[-]+>>>>>[>>>>>>>>>]>+<]]+>>>>[-<<<<->>>>]+<<<<[->>>>-<[-<<<+>>>]<<<[->>>+<<<<<<<<<<<<[<<<<<<<<<]>>>[-]+>>>>>>[>>>>>>>>>]>[-]+<]]+>[-<[>>>>>>>>>]<<<<<<<<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]<<<<<<<[->+>>>-<<<<]>>>>>>>>>++++++++++++++++++++++++++>>[-<<<<+>>>>]<<<<[->>>>+<<[-]<<]>>[<<<<<<<+<[-<+>>>>+<<[-]]>[-<<[->+>>>-<<<<]>>>]>>>>>>>>>>>>>[>>[-]>[-]>[-]>>>>>]<<<<<<<<<[<<<<<<<<<]>>>[-]>>>>>>[>>>>>[-<<<<+>>>>]<<<<[->>>>+<<<+<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>>[-<<<<<<<<<+>>>>>>>>>]>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>+++++++++++++++[[>>>>>>>>>]+>[-]>[-]>[-]>[-]>[-]>[-]>[-]>[-]>[-]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>-]+[>+>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>->>>>>[-<<<<<+>>>>>]<<<<<[->>>>>+<<<<<<[->>[-<<+>>]<<[->>+>+<<<]+>>>>>>>>>]<<<<<<<<[<<<<<<<<<]]>>>>>>>>>[>>>>>>>>>]<<<<<<<<<[>[->>>>>>>>>+<<<<<<<<<]<<<<<<<<<<]>[->>>>>>>>>+<<<<<<<<<]<+>>>>>>>>]<<<<<<<<<[>[-]<->>>[-<<<+>[<->-<<<<<<<+>>>>>>>]<[->+<]>>>]<<[->>+<<]<+<<<<<<<<<]>>>>>>>>>[>>>>>>[-<<<<<+>>>>>]<<<<<[->>>>>+<<<<+<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>+>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>->>>>>[-<<<<<+>>>>>]<<<<<[->>>>>+<<<<<<[->>[-<<+>>]<<[->>+>>+<<<<]+>>>>>>>>>]<<<<<<<<[<<<<<<<<<]]>>>>>>>>>[>>>>>>>>>]<<<<<<<<<[>[->>>>>>>>>+<<<<<<<<<]<<<<<<<<<<]>[->>>>>>>>>+<<<<<<<<<]<+>>>>>>>>]<<<<<<<<<[>[-]<->>>>[-<<<<+>[<->-<<<<<<+>>>>>>]<[->+<]>>>>]<<<[->>>+<<<]<+<<<<<<<<<]>>>>>>>>>[>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>+++++++++++++++[[>>>>>>>>>]<<<<<<<<<-<<<<<<<<<[<<<<<<<<<]>>>>>>>>>-]+[>>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<<[->>>>>>>+<<<<<<+<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>>>>>>[-]>>>]<<<<<<<<<[<<<<<<<<<]>>>>+>[-<-<<<<+>>>>>]>[-<<<<<<[->>>>>+<++<<<<]>>>>>[-<<<<<+>>>>>]<->+>]<[->+<]<<<<<[->>>>>+<<<<<]>>>>>>[-]<<<<<<+>>>>[-<<<<->>>>]+<<<<[->>>>->>>>>[>>[-<<->>]+<<[->>->[-<<<+>>>]<<<[->>>+<<<<<<<<<<<<[<<<<<<<<<]>>>[-]+>>>>>>[>>>>>>>>>]>+<]]+>>>[-<<<->>>]+<<<[->>>-<[-<<+>>]<<[->>+<<<<<<<<<<<[<<<<<<<<<]>>>>[-]+>>>>>[>>>>>>>>>]>[-]+<]]+>[-<[>>>>>>>>>]<<<<<<<<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>[-<<<<+>>>>]<<<<[->>>>+>>>>>[>+>>[-<<->>]<<[->>+<<]>>>>>>>>]<<<<<<<<+<[>[->>>>>+<<<<[->>>>-<<<<<<<<<<<<<<+>>>>>>>>>>>[->>>+<<<]<]>[->>>-<<<<<<<<<<<<<<+>>>>>>>>>>>]<<]>[->>>>+<<<[->>>-<<<<<<<<<<<<<<+>>>>>>>>>>>]<]>[->>>+<<<]<<<<<<<<<<<<]>>>>[-]<<<<]>>>[-<<<+>>>]<<<[->>>+>>>>>>[>+>[-<->]<[->+<]>>>>>>>>]<<<<<<<<+<[>[->>>>>+<<<[->>>-<<<<<<<<<<<<<<+>>>>>>>>>>[->>>>+<<<<]>]<[->>>>-<<<<<<<<<<<<<<+>>>>>>>>>>]<]>>[->>>+<<<<[->>>>-<<<<<<<<<<<<<<+>>>>>>>>>>]>]<[->>>>+<<<<]<<<<<<<<<<<]>>>>>>+<<<<<<]]>>>>[-<<<<+>>>>]<<<<[->>>>+>>>>>[>>>>>>>>>]<<<<<<<<<[>[->>>>>+<<<<[->>>>-<<<<<<<<<<<<<<+>>>>>>>>>>>[->>>+<<<]<]>[->>>-<<<<<<<<<<<<<<+>>>>>>>>>>>]<<]>[->>>>+<<<[->>>-<<<<<<<<<<<<<<+>>>>>>>>>>>]<]>[->>>+<<<]<<<<<<<<<<<<]]>[-]>>[-]>[-]>>>>>[>>[-]>[-]>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>>>>>[-<<<<+>>>>]<<<<[->>>>+<<<+<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>+++++++++++++++[[>>>>>>>>>]+>[-]>[-]>[-]>[-]>[-]>[-]>[-]>[-]>[-]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>-]+[>+>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>->>>>[-<<<<+>>>>]<<<<[->>>>+<<<<<[->>[-<<+>>]<<[->>+>+<<<]+>>>>>>>>>]<<<<<<<<[<<<<<<<<<]]>>>>>>>>>[>>>>>>>>>]<<<<<<<<<[>[->>>>>>>>>+<<<<<<<<<]<<<<<<<<<<]>[->>>>>>>>>+<<<<<<<<<]<+>>>>>>>>]<<<<<<<<<[>[-]<->>>[-<<<+>[<->-<<<<<<<+>>>>>>>]<[->+<]>>>]<<[->>+<<]<+<<<<<<<<<]>>>>>>>>>[>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>[-]>>>>+++++++++++++++[[>>>>>>>>>]<<<<<<<<<-<<<<<<<<<[<<<<<<<<<]>>>>>>>>>-]+[>>>[-<<<->>>]+<<<[->>>->[-<<<<+>>>>]<<<<[->>>>+<<<<<<<<<<<<<[<<<<<<<<<]>>>>[-]+>>>>>[>>>>>>>>>]>+<]]+>>>>[-<<<<->>>>]+<<<<[->>>>-<[-<<<+>>>]<<<[->>>+<<<<<<<<<<<<[<<<<<<<<<]>>>[-]+>>>>>>[>>>>>>>>>]>[-]+<]]+>[-<[>>>>>>>>>]<<<<<<<<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>[-<<<+>>>]<<<[->>>+>>>>>>[>+>>>[-<<<->>>]<<<[->>>+<<<]>>>>>>>>]<<<<<<<<+<[>[->+>[-<-<<<<<<<<<<+>>>>>>>>>>>>[-<<+>>]<]>[-<<-<<<<<<<<<<+>>>>>>>>>>>>]<<<]>>[-<+>>[-<<-<<<<<<<<<<+>>>>>>>>>>>>]<]>[-<<+>>]<<<<<<<<<<<<<]]>>>>[-<<<<+>>>>]<<<<[->>>>+>>>>>[>+>>[-<<->>]<<[->>+<<]>>>>>>>>]<<<<<<<<+<[>[->+>>[-<<-<<<<<<<<<<+>>>>>>>>>>>[-<+>]>]<[-<-<<<<<<<<<<+>>>>>>>>>>>]<<]>>>[-<<+>[-<-<<<<<<<<<<+>>>>>>>>>>>]>]<[-<+>]<<<<<<<<<<<<]>>>>>+<<<<<]>>>>>>>>>[>>>[-]>[-]>[-]>>>>]<<<<<<<<<[<<<<<<<<<]>>>[-]>[-]>>>>>[>>>>>>>[-<<<<<<+>>>>>>]<<<<<<[->>>>>>+<<<<+<<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>+>[-<-<<<<+>>>>>]>>[-<<<<<<<[->>>>>+<++<<<<]>>>>>[-<<<<<+>>>>>]<->+>>]<<[->>+<<]<<<<<[->>>>>+<<<<<]+>>>>[-<<<<->>>>]+<<<<[->>>>->>>>>[>>>[-<<<->>>]+<<<[->>>-<[-<<+>>]<<[->>+<<<<<<<<<<<[<<<<<<<<<]>>>>[-]+>>>>>[>>>>>>>>>]>+<]]+>>[-<<->>]+<<[->>->[-<<<+>>>]<<<[->>>+<<<<<<<<<<<<[<<<<<<<<<]>>>[-]+>>>>>>[>>>>>>>>>]>[-]+<]]+>[-<[>>>>>>>>>]<<<<<<<<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>[-<<<+>>>]<<<[->>>+>>>>>>[>+>[-<->]<[->+<]>>>>>>>>]<<<<<<<<+<[>[->>>>+<<[->>-<<<<<<<<<<<<<+>>>>>>>>>>[->>>+<<<]>]<[->>>-<<<<<<<<<<<<<+>>>>>>>>>>]<]>>[->>+<<<[->>>-<<<<<<<<<<<<<+>>>>>>>>>>]>]<[->>>+<<<]<<<<<<<<<<<]>>>>>[-]>>[-<<<<<<<+>>>>>>>]<<<<<<<[->>>>>>>+<<+<<<<<]]>>>>[-<<<<+>>>>]<<<<[->>>>+>>>>>[>+>>[-<<->>]<<[->>+<<]>>>>>>>>]<<<<<<<<+<[>[->>>>+<<<[->>>-<<<<<<<<<<<<<+>>>>>>>>>>>[->>+<<]<]>[->>-<<<<<<<<<<<<<+>>>>>>>>>>>]<<]>[->>>+<<[->>-<<<<<<<<<<<<<+>>>>>>>>>>>]<]>[->>+<<]<<<<<<<<<<<<]]>>>>[-]<<<<]>>>>[-<<<<+>>>>]<<<<[->>>>+>[-]>>[-<<<<<<<+>>>>>>>]<<<<<<<[->>>>>>>+<<+<<<<<]>>>>>>>>>[>>>>>>>>>]<<<<<<<<<[>[->>>>+<<<[->>>-<<<<<<<<<<<<<+>>>>>>>>>>>[->>+<<]<]>[->>-<<<<<<<<<<<<<+>>>>>>>>>>>]<<]>[->>>+<<[->>-<<<<<<<<<<<<<+>>>>>>>>>>>]<]>[->>+<<]<<<<<<<<<<<<]]>>>>>>>>>[>>[-]>[-]>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>[-]>[-]>>>>>[>>>>>[-<<<<+>>>>]<<<<[->>>>+<<<+<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>>>>>>[-<<<<<+>>>>>]<<<<<[->>>>>+<<<+<<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>+++++++++++++++[[>>>>>>>>>]+>[-]>[-]>[-]>[-]>[-]>[-]>[-]>[-]>[-]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>-]+[>+>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>->>>>[-<<<<+>>>>]<<<<[->>>>+<<<<<[->>[-<<+>>]<<[->>+>>+<<<<]+>>>>>>>>>]<<<<<<<<[<<<<<<<<<]]>>>>>>>>>[>>>>>>>>>]<<<<<<<<<[>[->>>>>>>>>+<<<<<<<<<]<<<<<<<<<<]>[->>>>>>>>>+<<<<<<<<<]<+>>>>>>>>]<<<<<<<<<[>[-]<->>>>[-<<<<+>[<->-<<<<<<+>>>>>>]<[->+<]>>>>]<<<[->>>+<<<]<+<<<<<<<<<]>>>>>>>>>[>+>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>->>>>>[-<<<<<+>>>>>]<<<<<[->>>>>+<<<<<<[->>>[-<<<+>>>]<<<[->>>+>+<<<<]+>>>>>>>>>]<<<<<<<<[<<<<<<<<<]]>>>>>>>>>[>>>>>>>>>]<<<<<<<<<[>>[->>>>>>>>>+<<<<<<<<<]<<<<<<<<<<<]>>[->>>>>>>>>+<<<<<<<<<]<<+>>>>>>>>]<<<<<<<<<[>[-]<->>>>[-<<<<+>[<->-<<<<<<+>>>>>>]<[->+<]>>>>]<<<[->>>+<<<]<+<<<<<<<<<]>>>>>>>>>[>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>+++++++++++++++[[>>>>>>>>>]<<<<<<<<<-<<<<<<<<<[<<<<<<<<<]>>>>>>>>>-]+>>>>>>>>>>>>>>>>>>>>>+<<<[<<<<<<<<<]>>>>>>>>>[>>>[-<<<->>>]+<<<[->>>->[-<<<<+>>>>]<<<<[->>>>+<<<<<<<<<<<<<[<<<<<<<<<]>>>>[-]+>>>>>[>>>>>>>>>]>+<]]+>>>>[-<<<<->>>>]+<<<<[->>>>-<[-<<<+>>>]<<<[->>>+<<<<<<<<<<<<[<<<<<<<<<]>>>[-]+>>>>>>[>>>>>>>>>]>[-]+<]]+>[-<[>>>>>>>>>]<<<<<<<<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>->>[-<<<<+>>>>]<<<<[->>>>+<<[-]<<]>>]<<+>>>>[-<<<<->>>>]+<<<<[->>>>-<<<<<<.>>]>>>>[-<<<<<<<.>>>>>>>]<<<[-]>[-]>[-]>[-]>[-]>[-]>>>[>[-]>[-]>[-]>[-]>[-]>[-]>>>]<<<<<<<<<[<<<<<<<<<]>>>>>>>>>[>>>>>[-]>>>>]<<<<<<<<<[<<<<<<<<<]>+++++++++++[-[->>>>>>>>>+<<<<<<<<<]>>>>>>>>>]>>>>+>>>>>>>>>+<<<<<<<<<<<<<<[<<<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<<[->>>>>>>+[-]>>[>>>>>>>>>]<<<<<<<<<[>>>>>>>[-<<<<<<+>>>>>>]<<<<<<[->>>>>>+<<<<<<<[<<<<<<<<<]>>>>>>>[-]+>>>]<<<<<<<<<<]]>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<<[->>>>>>>+>>[>+>>>>[-<<<<->>>>]<<<<[->>>>+<<<<]>>>>>>>>]<<+<<<<<<<[>>>>>[->>+<<]<<<<<<<<<<<<<<]>>>>>>>>>[>>>>>>>>>]<<<<<<<<<[>[-]<->>>>>>>[-<<<<<<<+>[<->-<<<+>>>]<[->+<]>>>>>>>]<<<<<<[->>>>>>+<<<<<<]<+<<<<<<<<<]>>>>>>>-<<<<[-]+<<<]+>>>>>>>[-<<<<<<<->>>>>>>]+<<<<<<<[->>>>>>>->>[>>>>>[->>+<<]>>>>]<<<<<<<<<[>[-]<->>>>>>>[-<<<<<<<+>[<->-<<<+>>>]<[->+<]>>>>>>>]<<<<<<[->>>>>>+<<<<<<]<+<<<<<<<<<]>+++++[-[->>>>>>>>>+<<<<<<<<<]>>>>>>>>>]>>>>+<<<<<[<<<<<<<<<]>>>>>>>>>[>>>>>[-<<<<<->>>>>]+<<<<<[->>>>>->>[-<<<<<<<+>>>>>>>]<<<<<<<[->>>>>>>+<<<<<<<<<<<<<<<<[<<<<<<<<<]>>>>[-]+>>>>>[>>>>>>>>>]>+<]]+>>>>>>>[-<<<<<<<->>>>>>>]+<<<<<<<[->>>>>>>-<<[-<<<<<+>>>>>]<<<<<[->>>>>+<<<<<<<<<<<<<<[<<<<<<<<<]>>>[-]+>>>>>>[>>>>>>>>>]>[-]+<]]+>[-<[>>>>>>>>>]<<<<<<<<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>[-]<<<+++++[-[->>>>>>>>>+<<<<<<<<<]>>>>>>>>>]>>>>-<<<<<[<<<<<<<<<]]>>>]<<<<.>>>>>>>>>>[>>>>>>[-]>>>]<<<<<<<<<[<<<<<<<<<]>++++++++++[-[->>>>>>>>>+<<<<<<<<<]>>>>>>>>>]>>>>>+>>>>>>>>>+<<<<<<<<<<<<<<<[<<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]<<<<<<<<[->>>>>>>>+[-]>[>>>>>>>>>]<<<<<<<<<[>>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<<[->>>>>>>+<<<<<<<<[<<<<<<<<<]>>>>>>>>[-]+>>]<<<<<<<<<<]]>>>>>>>>[-<<<<<<<<+>>>>>>>>]<<<<<<<<[->>>>>>>>+>[>+>>>>>[-<<<<<->>>>>]<<<<<[->>>>>+<<<<<]>>>>>>>>]<+<<<<<<<<[>>>>>>[->>+<<]<<<<<<<<<<<<<<<]>>>>>>>>>[>>>>>>>>>]<<<<<<<<<[>[-]<->>>>>>>>[-<<<<<<<<+>[<->-<<+>>]<[->+<]>>>>>>>>]<<<<<<<[->>>>>>>+<<<<<<<]<+<<<<<<<<<]>>>>>>>>-<<<<<[-]+<<<]+>>>>>>>>[-<<<<<<<<->>>>>>>>]+<<<<<<<<[->>>>>>>>->[>>>>>>[->>+<<]>>>]<<<<<<<<<[>[-]<->>>>>>>>[-<<<<<<<<+>[<->-<<+>>]<[->+<]>>>>>>>>]<<<<<<<[->>>>>>>+<<<<<<<]<+<<<<<<<<<]>+++++[-[->>>>>>>>>+<<<<<<<<<]>>>>>>>>>]>>>>>+>>>>>>>>>>>>>>>>>>>>>>>>>>>+<<<<<<[<<<<<<<<<]>>>>>>>>>[>>>>>>[-<<<<<<->>>>>>]+<<<<<<[->>>>>>->>[-<<<<<<<<+>>>>>>>>]<<<<<<<<[->>>>>>>>+<<<<<<<<<<<<<<<<<[<<<<<<<<<]>>>>[-]+>>>>>[>>>>>>>>>]>+<]]+>>>>>>>>[-<<<<<<<<->>>>>>>>]+<<<<<<<<[->>>>>>>>-<<[-<<<<<<+>>>>>>]<<<<<<[->>>>>>+<<<<<<<<<<<<<<<[<<<<<<<<<]>>>[-]+>>>>>>[>>>>>>>>>]>[-]+<]]+>[-<[>>>>>>>>>]<<<<<<<<]>>>>>>>>]<<<<<<<<<[<<<<<<<<<]>>>>[-]<<<+++++[-[->>>>>>>>>+<<<<<<<<<]>>>>>>>>>]>>>>>->>>>>>>>>>>>>>>>>>>>>>>>>>>-<<<<<<[<<<<<<<<<]]>>>]";
-	let fizzbuzz = b"++++++++++[>++++++++++<-]>>++++++++++>->>>>>>>>>>>>>>>>-->+++++++[->++++++++++<]>[->+>+>+>+<<<<]+++>>+++>>>++++++++[-<++++<++++<++++>>>]+++++[-<++++<++++>>]>>-->++++++[->+++++++++++<]>[->+>+>+>+<<<<]+++++>>+>++++++>++++++>++++++++[-<++++<++++<++++>>>]++++++[-<+++<+++<+++>>>]>>-->---+[-<+]-<[+[->+]-<<->>>+>[-]++[-->++]-->+++[---++[--<++]---->>-<+>[++++[----<++++]--[>]++[-->++]--<]>++[--+[-<+]->>[-]+++++[---->++++]-->[->+<]>>[.>]++[-->++]]-->+++]---+[-<+]->>-[+>>>+[-<+]->>>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]>>>++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[>++++++[-<++++++++>]<.<<+>+>[-]]<[<[->-<]++++++[->++++++++<]>.[-]]<<++++++[-<++++++++>]<.[-]<<[-<+>]+[-<+]->>]+[-]<<<.>>>+[-<+]-<<]";
+fn finish(options: &Options, tape: &Tape, elapsed: Duration, result: Result<(), RuntimeError>, report: Option<String>) {
+	let _ = io::stdout().flush();
 
-	let program = fizzbuzz.as_ref().parse();
+	if options.stats {
+		eprintln!("steps: {}, time: {:.3}s", tape.steps(), elapsed.as_secs_f64());
+	}
 
-	tape.run(&program);
-}
\ No newline at end of file
+	if let Some(report) = report {
+		eprint!("{}", report);
+	}
+
+	if let Err(e) = result {
+		fail(if e.aborted() { EXIT_ABORTED } else { EXIT_RUNTIME }, e)
+	}
+}
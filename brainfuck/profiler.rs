@@ -0,0 +1,186 @@
+use std::slice;
+use super::{instructions, ir, Command, Observer, Position, RuntimeErrorKind, Tape};
+use super::config::Overflow;
+use super::ir::Op;
+
+// loops longer than this are shortened in reports
+const SNIPPET_LENGTH: usize = 32;
+
+/* profiling */
+
+// counts how many times every instruction was executed; for loops, the count of the '['
+// is the number of times the loop was entered and that of the ']' the number of iterations
+pub struct Profiler {
+	hits: Vec<u64>,
+	// what the loops are optimized into depends on it
+	overflow: Overflow
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LoopProfile {
+	// the instruction positions of the brackets
+	pub start: usize,
+	pub end: usize,
+	pub entries: u64,
+	pub iterations: u64,
+	// including the steps of the loops nested in it
+	pub steps: u64,
+	// what the optimizer turns the loop into
+	pub optimized: &'static str
+}
+
+impl Profiler {
+	pub fn new(program: &[Command], overflow: Overflow) -> Profiler {
+		Profiler {
+			hits: vec![0; instructions(program).len()],
+			overflow: overflow
+		}
+	}
+
+	pub fn hits(&self) -> &[u64] { &self.hits }
+
+	pub fn total(&self) -> u64 { self.hits.iter().sum() }
+
+	// every loop of the program, the ones that took the most steps first
+	pub fn loops(&self, program: &[Command]) -> Vec<LoopProfile> {
+		let mut loops = Vec::new();
+		self.collect_loops(program, 0, &mut loops);
+		loops.sort_by(|a, b| b.steps.cmp(&a.steps).then(a.start.cmp(&b.start)));
+
+		loops
+	}
+
+	// returns the instruction position following the commands
+	fn collect_loops(&self, commands: &[Command], mut pc: usize, loops: &mut Vec<LoopProfile>) -> usize {
+		for command in commands {
+			match *command {
+				Command::Loop(ref body) => {
+					let end = self.collect_loops(body, pc + 1, loops);
+					loops.push(LoopProfile {
+						start: pc,
+						end: end,
+						entries: self.hits[pc],
+						iterations: self.hits[end],
+						steps: self.hits[pc..end + 1].iter().sum(),
+						optimized: optimized(command, self.overflow)
+					});
+					pc = end;
+				},
+				Command::Procedure(ref body) => pc = self.collect_loops(body, pc + 1, loops),
+				_ => {}
+			}
+			pc += 1;
+		}
+
+		pc
+	}
+
+	// `positions` maps instruction positions to the source, see instruction_positions
+	pub fn report(&self, program: &[Command], positions: &[Position], limit: usize) -> String {
+		let source = instructions(program);
+		let total = self.total();
+		let mut report = format!("{} steps\n", total);
+
+		report.push_str("hottest loops:\n");
+		report.push_str(&format!("{:>12} {:>7} {:>10} {:>12}  {:<15} {:<14} {}\n",
+			"steps", "share", "entries", "iterations", "source", "optimized", "loop"));
+
+		for profile in self.loops(program).iter().take(limit) {
+			let (start, end) = (positions[profile.start], positions[profile.end]);
+			let span = format!("{}:{}-{}:{}", start.line, start.column, end.line, end.column);
+			let share = if total == 0 { 0.0 } else { 100.0 * profile.steps as f64 / total as f64 };
+
+			report.push_str(&format!("{:>12} {:>6.2}% {:>10} {:>12}  {:<15} {:<14} {}\n",
+				profile.steps, share, profile.entries, profile.iterations, span, profile.optimized,
+				snippet(&source[profile.start..profile.end + 1])));
+		}
+
+		report
+	}
+}
+
+impl Observer for Profiler {
+	fn observe(&mut self, _: &Tape, pc: usize) -> Result<(), RuntimeErrorKind> {
+		self.hits[pc] += 1;
+		Ok(())
+	}
+}
+
+fn optimized(command: &Command, overflow: Overflow) -> &'static str {
+	if command.uses_procedures() { return "none"; }
+
+	match ir::optimize(slice::from_ref(command), overflow)[..] {
+		[Op::SetZero] => "clear",
+		[Op::Scan(_)] => "scan",
+		[Op::Loop(_)] => "loop",
+		_ => "multiplication"
+	}
+}
+
+fn snippet(code: &[u8]) -> String {
+	if code.len() <= SNIPPET_LENGTH {
+		String::from_utf8_lossy(code).into_owned()
+	} else {
+		format!("{}...", String::from_utf8_lossy(&code[..SNIPPET_LENGTH - 3]))
+	}
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use super::{LoopProfile, Profiler};
+	use super::super::{instruction_positions, Dialect, Interpreter, Program};
+	use super::super::config::{Overflow, TapeConfig};
+
+	fn run_profiled(program: &Program) -> Profiler {
+		let mut interpreter = Interpreter::new(io::empty(), io::sink(), TapeConfig::default());
+		let mut profiler = Profiler::new(program.commands(), Overflow::Wrapping);
+		interpreter.run_observed(program, &mut profiler).unwrap();
+		profiler
+	}
+
+	fn profile(source: &[u8]) -> (Profiler, Vec<LoopProfile>) {
+		let program = Program::parse(source).unwrap();
+		let profiler = run_profiled(&program);
+		let loops = profiler.loops(program.commands());
+
+		(profiler, loops)
+	}
+
+	#[test]
+	fn counting_hits() {
+		let (profiler, _) = profile(b"++[->+<]");
+
+		assert_eq!(profiler.hits(), &[1, 1, 1, 2, 2, 2, 2, 2]);
+		assert_eq!(profiler.total(), 13);
+	}
+
+	#[test]
+	fn profiling_loops() {
+		let (_, loops) = profile(b"+++[>++[-]<-]>[-]>[>]");
+
+		assert_eq!(loops, vec![
+			LoopProfile { start: 3, end: 12, entries: 1, iterations: 3, steps: 34, optimized: "loop" },
+			LoopProfile { start: 7, end: 9, entries: 3, iterations: 6, steps: 15, optimized: "clear" },
+			LoopProfile { start: 14, end: 16, entries: 1, iterations: 0, steps: 1, optimized: "clear" },
+			LoopProfile { start: 18, end: 20, entries: 1, iterations: 0, steps: 1, optimized: "scan" }
+		]);
+	}
+
+	#[test]
+	fn reports() {
+		let source = b"+++\n[->+<]\n>[\n-\n]";
+		let program = Program::parse(source).unwrap();
+		let positions = instruction_positions(source, &Dialect::default());
+		let report = run_profiled(&program).report(program.commands(), &positions, 1);
+
+		assert_eq!(report.lines().collect::<Vec<_>>(), vec![
+			"27 steps",
+			"hottest loops:",
+			"       steps   share    entries   iterations  source          optimized      loop",
+			"          16  59.26%          1            3  2:1-2:6         multiplication [->+<]"
+		]);
+	}
+}
@@ -0,0 +1,352 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+use super::{Interpreter, RuntimeError, Tape};
+use super::config::{CellWidth, EofPolicy, Overflow, TapeConfig};
+use super::vm::Bytecode;
+use self::SnapshotError::*;
+
+const HEADER: &str = "brainfuck snapshot";
+
+/* snapshots */
+
+// everything needed to continue a run of some bytecode, besides the input; as the VM's loops need
+// no stack, the instruction position is all there is to where the run stopped
+#[derive(Debug, PartialEq, Clone)]
+pub struct Snapshot {
+	// tells apart the bytecode the snapshot was taken of, see fingerprint
+	pub program: u64,
+	pub config: TapeConfig,
+	pub cells: Vec<i64>,
+	pub pos: usize,
+	pub steps: u64,
+	pub pc: usize,
+	// the number of input bytes read so far
+	pub input: u64,
+	// the output produced so far
+	pub output: Vec<u8>
+}
+
+// FNV-1a over the instructions, so that it stays the same between builds
+pub fn fingerprint(bytecode: &Bytecode) -> u64 {
+	format!("{:?}", bytecode.code()).bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+		(hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+	})
+}
+
+impl Snapshot {
+	pub fn matches(&self, bytecode: &Bytecode) -> bool {
+		self.program == fingerprint(bytecode) && self.pc <= bytecode.code().len()
+	}
+}
+
+impl<R: Read, W: Write> Interpreter<R, W> {
+	// the state of a run of the bytecode stopped by its budget, given the output it produced; runs
+	// that failed can't be continued
+	pub fn snapshot(&self, bytecode: &Bytecode, error: &RuntimeError, output: &[u8]) -> Option<Snapshot> {
+		if !error.aborted() { return None; }
+
+		Some(Snapshot {
+			program: fingerprint(bytecode),
+			config: self.tape.config,
+			cells: self.tape.array.clone(),
+			pos: self.tape.pos,
+			// the step that was refused gets taken again when resuming
+			steps: self.tape.steps.saturating_sub(1),
+			pc: bytecode.instruction_at(error.position)?,
+			input: self.consumed,
+			output: output.to_vec()
+		})
+	}
+
+	// continues the run the snapshot was taken of, given the same input as that run; the input read
+	// before the snapshot is skipped and the output produced before it is written again
+	pub fn resume(&mut self, bytecode: &Bytecode, snapshot: &Snapshot) -> Result<(), RuntimeError> {
+		assert!(snapshot.matches(bytecode), "the snapshot wasn't taken of this bytecode");
+
+		self.tape = Tape {
+			array: snapshot.cells.clone(),
+			pos: snapshot.pos,
+			config: snapshot.config,
+			steps: snapshot.steps
+		};
+		let skipped = io::copy(&mut (&mut self.input).take(snapshot.input), &mut io::sink());
+		self.consumed = skipped.unwrap_or(0);
+		let _ = self.output.write_all(&snapshot.output);
+
+		self.run_bytecode_from(bytecode, snapshot.pc)
+	}
+}
+
+/* writing and reading */
+
+// a writer keeping a copy of everything written to it, for taking snapshots
+pub struct Recorder<W> {
+	inner: W,
+	recorded: Vec<u8>
+}
+
+impl<W: Write> Recorder<W> {
+	pub fn new(inner: W) -> Recorder<W> {
+		Recorder { inner: inner, recorded: Vec::new() }
+	}
+
+	pub fn recorded(&self) -> &[u8] { &self.recorded }
+}
+
+impl<W: Write> Write for Recorder<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let written = self.inner.write(buf)?;
+		self.recorded.extend_from_slice(&buf[..written]);
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> io::Result<()> { self.inner.flush() }
+}
+
+// a line per field, with only the cells that aren't zero listed
+impl fmt::Display for Snapshot {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let config = &self.config;
+
+		writeln!(f, "{}", HEADER)?;
+		writeln!(f, "program {:016x}", self.program)?;
+		writeln!(f, "width {}", match config.width {
+			CellWidth::U8 => 8,
+			CellWidth::U16 => 16,
+			CellWidth::U32 => 32,
+			CellWidth::I64 => 64
+		})?;
+		writeln!(f, "overflow {}", match config.overflow {
+			Overflow::Wrapping => "wrap",
+			Overflow::Saturating => "saturate",
+			Overflow::Checked => "error"
+		})?;
+		writeln!(f, "eof {}", match config.eof {
+			EofPolicy::Unchanged => "unchanged",
+			EofPolicy::Zero => "zero",
+			EofPolicy::MinusOne => "minus-one",
+			EofPolicy::Error => "error"
+		})?;
+		writeln!(f, "growable {}", config.growable)?;
+		writeln!(f, "wrap-pointer {}", config.wrap_pointer)?;
+		writeln!(f, "cells {}", self.cells.len())?;
+		for (i, &value) in self.cells.iter().enumerate().filter(|&(_, &value)| value != 0) {
+			writeln!(f, "cell {} {}", i, value)?;
+		}
+		writeln!(f, "pos {}", self.pos)?;
+		writeln!(f, "steps {}", self.steps)?;
+		writeln!(f, "pc {}", self.pc)?;
+		writeln!(f, "input {}", self.input)?;
+		write!(f, "output ")?;
+		for byte in &self.output {
+			write!(f, "{:02x}", byte)?;
+		}
+		writeln!(f)
+	}
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SnapshotError {
+	NotASnapshot,
+	// the number of the line that couldn't be read
+	InvalidLine(usize),
+	Missing(&'static str)
+}
+
+impl fmt::Display for SnapshotError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			NotASnapshot => write!(f, "not a snapshot"),
+			InvalidLine(line) => write!(f, "invalid snapshot line {}", line),
+			Missing(field) => write!(f, "the snapshot has no {}", field)
+		}
+	}
+}
+
+impl Error for SnapshotError {}
+
+impl Snapshot {
+	// the tape is allocated before anything else is checked, so the file can't be allowed to ask for
+	// more cells than a run would be given
+	pub fn parse(text: &str, max_cells: usize) -> Result<Snapshot, SnapshotError> {
+		let mut lines = text.lines();
+		if lines.next() != Some(HEADER) { return Err(NotASnapshot); }
+
+		let mut config = TapeConfig::classic();
+		let (mut program, mut cells, mut pos, mut steps, mut pc, mut input, mut output) =
+			(None, None, None, None, None, None, None);
+
+		for (i, line) in lines.enumerate() {
+			let invalid = InvalidLine(i + 2);
+			let mut words = line.split(' ');
+			let (key, value) = (words.next().unwrap(), words.next().ok_or(invalid)?);
+
+			match key {
+				"program" => program = Some(u64::from_str_radix(value, 16).map_err(|_| invalid)?),
+				"width" => config.width = match value {
+					"8" => CellWidth::U8,
+					"16" => CellWidth::U16,
+					"32" => CellWidth::U32,
+					"64" => CellWidth::I64,
+					_ => return Err(invalid)
+				},
+				"overflow" => config.overflow = match value {
+					"wrap" => Overflow::Wrapping,
+					"saturate" => Overflow::Saturating,
+					"error" => Overflow::Checked,
+					_ => return Err(invalid)
+				},
+				"eof" => config.eof = match value {
+					"unchanged" => EofPolicy::Unchanged,
+					"zero" => EofPolicy::Zero,
+					"minus-one" => EofPolicy::MinusOne,
+					"error" => EofPolicy::Error,
+					_ => return Err(invalid)
+				},
+				"growable" => config.growable = value.parse().map_err(|_| invalid)?,
+				"wrap-pointer" => config.wrap_pointer = value.parse().map_err(|_| invalid)?,
+				"cells" => match value.parse() {
+					Ok(length) if length <= max_cells => cells = Some(vec![0; length]),
+					_ => return Err(invalid)
+				},
+				"cell" => {
+					let index: usize = value.parse().map_err(|_| invalid)?;
+					let value = words.next().and_then(|value| value.parse().ok()).ok_or(invalid)?;
+					match cells.as_mut().and_then(|cells: &mut Vec<i64>| cells.get_mut(index)) {
+						Some(cell) => *cell = value,
+						None => return Err(invalid)
+					}
+				},
+				"pos" => pos = Some(value.parse().map_err(|_| invalid)?),
+				"steps" => steps = Some(value.parse().map_err(|_| invalid)?),
+				"pc" => pc = Some(value.parse().map_err(|_| invalid)?),
+				"input" => input = Some(value.parse().map_err(|_| invalid)?),
+				"output" => output = Some(hex(value).ok_or(invalid)?),
+				_ => return Err(invalid)
+			}
+		}
+
+		let cells = cells.filter(|cells| !cells.is_empty()).ok_or(Missing("cells"))?;
+		let pos = pos.filter(|&pos| pos < cells.len()).ok_or(Missing("pointer position"))?;
+		config.cells = cells.len();
+
+		Ok(Snapshot {
+			program: program.ok_or(Missing("program"))?,
+			config: config,
+			cells: cells,
+			pos: pos,
+			steps: steps.ok_or(Missing("steps"))?,
+			pc: pc.ok_or(Missing("instruction position"))?,
+			input: input.ok_or(Missing("input"))?,
+			output: output.ok_or(Missing("output"))?
+		})
+	}
+}
+
+impl FromStr for Snapshot {
+	type Err = SnapshotError;
+
+	fn from_str(text: &str) -> Result<Snapshot, SnapshotError> {
+		Snapshot::parse(text, TapeConfig::default().cells)
+	}
+}
+
+fn hex(text: &str) -> Option<Vec<u8>> {
+	if !text.len().is_multiple_of(2) { return None; }
+
+	(0..text.len()).step_by(2).map(|i| text.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok())).collect()
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use super::{Snapshot, SnapshotError};
+	use super::super::{Interpreter, FIZZBUZZ};
+	use super::super::budget::Budget;
+	use super::super::config::{EofPolicy, Overflow, TapeConfig};
+	use super::super::vm::Bytecode;
+
+	fn config() -> TapeConfig {
+		TapeConfig { cells: 100, eof: EofPolicy::Zero, ..TapeConfig::classic() }
+	}
+
+	fn interrupted(source: &[u8], input: &[u8], max_steps: u64) -> (Snapshot, Interpreter<io::Cursor<Vec<u8>>, Vec<u8>>) {
+		let bytecode = Bytecode::compile(source, Overflow::Wrapping).unwrap();
+		let mut interpreter = Interpreter::new(io::Cursor::new(input.to_vec()), Vec::new(), config());
+		interpreter.set_budget(Budget { max_steps: Some(max_steps), ..Budget::unlimited() });
+
+		let error = interpreter.run_bytecode(&bytecode).unwrap_err();
+		let snapshot = interpreter.snapshot(&bytecode, &error, interpreter.output()).unwrap();
+
+		(snapshot, Interpreter::new(io::Cursor::new(input.to_vec()), Vec::new(), TapeConfig::default()))
+	}
+
+	fn uninterrupted(source: &[u8], input: &[u8]) -> Interpreter<io::Cursor<Vec<u8>>, Vec<u8>> {
+		let mut interpreter = Interpreter::new(io::Cursor::new(input.to_vec()), Vec::new(), config());
+		interpreter.run_bytecode(&Bytecode::compile(source, Overflow::Wrapping).unwrap()).unwrap();
+
+		interpreter
+	}
+
+	#[test]
+	fn resuming() {
+		let sources: &[(&[u8], &[u8])] = &[(FIZZBUZZ, b""), (b",[.,]", b"echo"), (b"+[>+<+]>[<]+[>]", b"")];
+
+		for &(source, input) in sources {
+			let bytecode = Bytecode::compile(source, Overflow::Wrapping).unwrap();
+			let expected = uninterrupted(source, input);
+
+			let steps = expected.tape().steps();
+			for &max_steps in &[0, 1, 7, steps / 3, steps - 1] {
+				let (snapshot, mut resumed) = interrupted(source, input, max_steps);
+				resumed.resume(&bytecode, &snapshot).unwrap();
+
+				assert_eq!(resumed.tape(), expected.tape());
+				assert_eq!(resumed.output(), expected.output());
+				assert_eq!(resumed.consumed(), expected.consumed());
+			}
+		}
+	}
+
+	#[test]
+	fn only_aborted_runs() {
+		let bytecode = Bytecode::compile(b"<", Overflow::Wrapping).unwrap();
+		let mut interpreter = Interpreter::new(io::empty(), Vec::new(), TapeConfig::default());
+		let error = interpreter.run_bytecode(&bytecode).unwrap_err();
+
+		assert!(interpreter.snapshot(&bytecode, &error, &[]).is_none());
+	}
+
+	#[test]
+	fn serializing() {
+		let (snapshot, _) = interrupted(b",[.,]", b"echo", 10);
+		let text = snapshot.to_string();
+
+		assert!(text.starts_with("brainfuck snapshot\nprogram "));
+		assert!(text.contains("\ncells 100\ncell 0 111\npos 0\nsteps 10\npc 4\ninput 4\noutput 656368\n"));
+		assert_eq!(text.parse(), Ok(snapshot));
+		assert!(Snapshot::matches(&text.parse().unwrap(), &Bytecode::compile(b",[.,]", Overflow::Wrapping).unwrap()));
+		assert!(!Snapshot::matches(&text.parse().unwrap(), &Bytecode::compile(b",[,.]", Overflow::Wrapping).unwrap()));
+	}
+
+	#[test]
+	fn invalid_snapshots() {
+		let (snapshot, _) = interrupted(b"+[]", b"", 10);
+		let text = snapshot.to_string();
+
+		assert_eq!("hello".parse::<Snapshot>(), Err(SnapshotError::NotASnapshot));
+		assert_eq!(text.replace("pos 0", "pos x").parse::<Snapshot>(), Err(SnapshotError::InvalidLine(10)));
+		assert_eq!(text.replace("cell 0 1", "cell 100 1").parse::<Snapshot>(), Err(SnapshotError::InvalidLine(9)));
+		assert_eq!(text.replace("steps", "# steps").parse::<Snapshot>(), Err(SnapshotError::InvalidLine(11)));
+		// the tape can't be longer than the one the snapshot is resumed with
+		assert_eq!(text.replace("cells 100", "cells 99999999999999").parse::<Snapshot>(), Err(SnapshotError::InvalidLine(8)));
+		assert_eq!(Snapshot::parse(&text, 99), Err(SnapshotError::InvalidLine(8)));
+		assert_eq!(Snapshot::parse(&text, 100), text.parse());
+		let without_pc: Vec<&str> = text.lines().filter(|line| !line.starts_with("pc ")).collect();
+		assert_eq!(without_pc.join("\n").parse::<Snapshot>(), Err(SnapshotError::Missing("instruction position")));
+	}
+}
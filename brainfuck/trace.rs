@@ -0,0 +1,228 @@
+use std::io::{self, BufReader, Read, Write};
+use super::{instructions, Command, Observer, RuntimeErrorKind, Tape};
+
+// the start of binary traces, followed by their version
+const MAGIC: &[u8] = b"BFTRACE";
+const VERSION: u8 = 1;
+
+// the number of cells shown on either side of the pointer in strips
+const RADIUS: usize = 5;
+
+/* tracing */
+
+// the state of the tape right before an instruction was executed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Event {
+	pub step: u64,
+	pub pc: usize,
+	pub instruction: u8,
+	pub pos: usize,
+	pub cell: i64
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+	// the magic and the version, then every event as varints; steps go up by one, so they're left out
+	Binary,
+	// a JSON object per event and line
+	JsonLines,
+	// a line per event, showing the cells around the pointer for watching the tape in a terminal
+	Strip
+}
+
+// writes an event for every instruction executed, including every check of a loop's condition
+pub struct Tracer<W> {
+	source: Vec<u8>,
+	format: Format,
+	output: W,
+	started: bool
+}
+
+impl<W: Write> Tracer<W> {
+	pub fn new(program: &[Command], format: Format, output: W) -> Tracer<W> {
+		Tracer {
+			source: instructions(program),
+			format: format,
+			output: output,
+			started: false
+		}
+	}
+
+	pub fn into_output(self) -> W { self.output }
+
+	fn write(&mut self, event: &Event, tape: &Tape) -> io::Result<()> {
+		match self.format {
+			Format::Binary => {
+				if !self.started {
+					self.output.write_all(MAGIC)?;
+					self.output.write_all(&[VERSION])?;
+				}
+				let mut record = Vec::new();
+				write_varint(&mut record, event.pc as u64);
+				record.push(event.instruction);
+				write_varint(&mut record, event.pos as u64);
+				// zigzag, so that small negative values stay short
+				write_varint(&mut record, ((event.cell << 1) ^ (event.cell >> 63)) as u64);
+				self.output.write_all(&record)
+			},
+			Format::JsonLines => writeln!(self.output, "{}", json(event)),
+			Format::Strip => writeln!(self.output, "{:>10} {:>6} {} {}", event.step, event.pc, event.instruction as char,
+				strip(tape, RADIUS).trim_end())
+		}
+	}
+}
+
+impl<W: Write> Observer for Tracer<W> {
+	fn observe(&mut self, tape: &Tape, pc: usize) -> Result<(), RuntimeErrorKind> {
+		let event = Event {
+			step: tape.steps(),
+			pc: pc,
+			instruction: self.source[pc],
+			pos: tape.pos(),
+			cell: tape.cell()
+		};
+		self.write(&event, tape).map_err(|e| RuntimeErrorKind::Output(e.kind()))?;
+		self.started = true;
+
+		Ok(())
+	}
+}
+
+pub fn json(event: &Event) -> String {
+	format!("{{\"step\":{},\"pc\":{},\"instruction\":\"{}\",\"pos\":{},\"cell\":{}}}",
+		event.step, event.pc, event.instruction as char, event.pos, event.cell)
+}
+
+// the cells around the pointer in columns of the same width, so that the pointer stays in place
+// while the tape scrolls past it
+fn strip(tape: &Tape, radius: usize) -> String {
+	let cells = tape.cells();
+	let pos = tape.pos() as isize;
+
+	(pos - radius as isize..pos + radius as isize + 1).map(|i| {
+		match cells.get(i as usize) {
+			Some(value) if i >= 0 && i == pos => format!("[{:>3}]", value),
+			Some(value) if i >= 0 => format!(" {:>3} ", value),
+			_ => "     ".to_owned()
+		}
+	}).collect::<Vec<_>>().concat()
+}
+
+/* reading binary traces */
+
+pub fn read_binary<R: Read>(input: R) -> io::Result<Vec<Event>> {
+	let mut bytes = BufReader::new(input).bytes();
+	let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a binary trace");
+
+	let mut header = Vec::new();
+	for _ in 0..MAGIC.len() + 1 {
+		header.push(bytes.next().ok_or_else(invalid)??);
+	}
+	if &header[..MAGIC.len()] != MAGIC || header[MAGIC.len()] != VERSION { return Err(invalid()); }
+
+	let mut events = Vec::new();
+	loop {
+		let pc = match read_varint(&mut bytes)? {
+			Some(pc) => pc as usize,
+			None => return Ok(events)
+		};
+		let instruction = bytes.next().ok_or_else(invalid)??;
+		let pos = read_varint(&mut bytes)?.ok_or_else(invalid)? as usize;
+		let zigzag = read_varint(&mut bytes)?.ok_or_else(invalid)?;
+
+		events.push(Event {
+			step: events.len() as u64 + 1,
+			pc: pc,
+			instruction: instruction,
+			pos: pos,
+			cell: (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64)
+		});
+	}
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+	while n >= 0x80 {
+		out.push(n as u8 | 0x80);
+		n >>= 7;
+	}
+	out.push(n as u8);
+}
+
+// None at the end of the input
+fn read_varint<I: Iterator<Item = io::Result<u8>>>(bytes: &mut I) -> io::Result<Option<u64>> {
+	let mut n = 0;
+
+	for shift in (0..64).step_by(7) {
+		let byte = match bytes.next() {
+			Some(byte) => byte?,
+			None if shift == 0 => return Ok(None),
+			None => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the trace was cut short"))
+		};
+		n |= ((byte & 0x7f) as u64) << shift;
+		if byte & 0x80 == 0 { return Ok(Some(n)); }
+	}
+
+	Err(io::Error::new(io::ErrorKind::InvalidData, "a number in the trace is too long"))
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use super::{json, read_binary, Event, Format, Tracer};
+	use super::super::{Interpreter, Program};
+	use super::super::config::{CellWidth, TapeConfig};
+
+	fn trace(source: &[u8], format: Format, config: TapeConfig) -> Vec<u8> {
+		let program = Program::parse(source).unwrap();
+		let mut tracer = Tracer::new(program.commands(), format, Vec::new());
+		Interpreter::new(io::empty(), io::sink(), config).run_observed(&program, &mut tracer).unwrap();
+
+		tracer.into_output()
+	}
+
+	#[test]
+	fn json_lines() {
+		let lines = String::from_utf8(trace(b"+[-]>", Format::JsonLines, TapeConfig::default())).unwrap();
+
+		assert_eq!(lines.lines().collect::<Vec<_>>(), vec![
+			"{\"step\":1,\"pc\":0,\"instruction\":\"+\",\"pos\":0,\"cell\":0}",
+			"{\"step\":2,\"pc\":1,\"instruction\":\"[\",\"pos\":0,\"cell\":1}",
+			"{\"step\":3,\"pc\":2,\"instruction\":\"-\",\"pos\":0,\"cell\":1}",
+			"{\"step\":4,\"pc\":3,\"instruction\":\"]\",\"pos\":0,\"cell\":0}",
+			"{\"step\":5,\"pc\":4,\"instruction\":\">\",\"pos\":0,\"cell\":0}"
+		]);
+	}
+
+	#[test]
+	fn binary_traces() {
+		let wide = TapeConfig { width: CellWidth::I64, ..TapeConfig::default() };
+		let source = b"--->+++++++++++[<------------>-]<[>+<+]>";
+		let binary = trace(source, Format::Binary, wide);
+		let lines = String::from_utf8(trace(source, Format::JsonLines, wide)).unwrap();
+		let events = read_binary(&binary[..]).unwrap();
+
+		assert_eq!(events.iter().map(json).collect::<Vec<_>>(), lines.lines().collect::<Vec<_>>());
+		assert!(events.iter().any(|event| event.cell == -135));
+		// a few bytes per event
+		assert!(binary.len() < 5 * events.len());
+
+		assert_eq!(read_binary(&b"BFTRACE\x01\x00+\x00\x02"[..]).unwrap(),
+			vec![Event { step: 1, pc: 0, instruction: b'+', pos: 0, cell: 1 }]);
+		assert!(read_binary(&b"BFTRACE\x01\x00+\x80"[..]).is_err());
+		assert!(read_binary(&b"trace"[..]).is_err());
+	}
+
+	#[test]
+	fn strips() {
+		let strip = String::from_utf8(trace(b">>+<", Format::Strip, TapeConfig::default())).unwrap();
+
+		assert_eq!(strip.lines().collect::<Vec<_>>(), vec![
+			"         1      0 >                          [  0]   0    0    0    0    0",
+			"         2      1 >                        0 [  0]   0    0    0    0    0",
+			"         3      2 +                   0    0 [  0]   0    0    0    0    0",
+			"         4      3 <                   0    0 [  1]   0    0    0    0    0"
+		]);
+	}
+}
@@ -0,0 +1,313 @@
+use std::fmt::Write;
+use super::config::{CellWidth, EofPolicy, Overflow, TapeConfig};
+use super::ir::Op;
+use super::ir::Op::*;
+
+// the exit code of a transpiled program that hits a runtime error, the same as the runner's
+const EXIT_RUNTIME: i32 = 4;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Target {
+	C,
+	Rust
+}
+
+/* transpiling */
+
+// emits a standalone program that behaves like the given one run on a tape with the given
+// config; execution budgets don't apply to transpiled programs
+pub fn transpile(ops: &[Op], config: &TapeConfig, target: Target) -> String {
+	let mut out = String::new();
+
+	match target {
+		Target::C => {
+			c_prelude(&mut out, config);
+			emit(&mut out, ops, target, 1);
+			out.push_str("\tfflush(stdout);\n\treturn 0;\n}\n");
+		},
+		Target::Rust => {
+			rust_prelude(&mut out, config);
+			emit(&mut out, ops, target, 1);
+			out.push_str("\tt.output.flush().unwrap();\n}\n");
+		}
+	}
+
+	out
+}
+
+fn emit(out: &mut String, ops: &[Op], target: Target, depth: usize) {
+	for op in ops {
+		let indent = "\t".repeat(depth);
+		let line = match (target, op) {
+			(Target::C, &Add(n)) => format!("add(pos, {});", n),
+			(Target::C, &Move(n)) => format!("pos = offset({});", n),
+			(Target::C, &SetZero) => "tape[pos] = 0;".to_owned(),
+			(Target::C, &MulAdd { offset, factor }) => format!("mul_add({}, {});", offset, factor),
+			(Target::C, &Scan(n)) => format!("while (tape[pos]) pos = offset({});", n),
+			(Target::C, &Output) => "output();".to_owned(),
+			(Target::C, &Input) => "input();".to_owned(),
+			(Target::C, &Loop(_)) => "while (tape[pos]) {".to_owned(),
+			(Target::Rust, &Add(n)) => format!("t.add(t.pos, {});", n),
+			(Target::Rust, &Move(n)) => format!("t.pos = t.offset({});", n),
+			(Target::Rust, &SetZero) => "t.cells[t.pos] = 0;".to_owned(),
+			(Target::Rust, &MulAdd { offset, factor }) => format!("t.mul_add({}, {});", offset, factor),
+			(Target::Rust, &Scan(n)) => format!("while t.cells[t.pos] != 0 {{ t.pos = t.offset({}); }}", n),
+			(Target::Rust, &Output) => "t.output();".to_owned(),
+			(Target::Rust, &Input) => "t.input();".to_owned(),
+			(Target::Rust, &Loop(_)) => "while t.cells[t.pos] != 0 {".to_owned()
+		};
+		let _ = writeln!(out, "{}{}", indent, line);
+
+		if let Loop(ref body) = *op {
+			emit(out, body, target, depth + 1);
+			let _ = writeln!(out, "{}}}", indent);
+		}
+	}
+}
+
+/* C */
+
+fn c_prelude(out: &mut String, config: &TapeConfig) {
+	let (cell, max) = match config.width {
+		CellWidth::U8 => ("uint8_t", "UINT8_MAX"),
+		CellWidth::U16 => ("uint16_t", "UINT16_MAX"),
+		CellWidth::U32 => ("uint32_t", "UINT32_MAX"),
+		CellWidth::I64 => ("int64_t", "INT64_MAX")
+	};
+
+	let _ = write!(out, "\
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+typedef {} cell;
+
+static cell *tape;
+static size_t len = {};
+static size_t pos = 0;
+
+static void fail(const char *message) {{
+	fflush(stdout);
+	fprintf(stderr, \"error: %s\\n\", message);
+	exit({});
+}}
+
+", cell, config.cells, EXIT_RUNTIME);
+
+	out.push_str("static inline size_t offset(ptrdiff_t n) {\n\tptrdiff_t target = (ptrdiff_t)pos + n;\n\n");
+	out.push_str("\tif (target >= 0 && (size_t)target < len) return (size_t)target;\n");
+	if config.growable {
+		out.push_str("\tif (target >= 0) {
+		cell *grown = realloc(tape, ((size_t)target + 1) * sizeof(cell));
+		if (!grown) fail(\"out of memory\");
+		for (size_t i = len; i <= (size_t)target; i++) grown[i] = 0;
+		tape = grown;
+		len = (size_t)target + 1;
+		return (size_t)target;
+	}
+");
+	}
+	if config.wrap_pointer {
+		out.push_str("\treturn (size_t)((target % (ptrdiff_t)len + (ptrdiff_t)len) % (ptrdiff_t)len);\n}\n\n");
+	} else {
+		out.push_str("\tfail(target < 0 ? \"the pointer moved left of the first cell\" : \"the pointer moved past the last cell\");\n");
+		out.push_str("\treturn 0;\n}\n\n");
+	}
+
+	// the arithmetic is done on 64 bits, narrower cells are brought into range afterwards
+	out.push_str("static inline void add(size_t at, int64_t delta) {\n\tint64_t value;\n\n");
+	match config.overflow {
+		Overflow::Wrapping => out.push_str("\t__builtin_add_overflow((int64_t)tape[at], delta, &value);\n"),
+		Overflow::Saturating => out.push_str(
+			"\tif (__builtin_add_overflow((int64_t)tape[at], delta, &value)) value = delta < 0 ? INT64_MIN : INT64_MAX;\n"),
+		Overflow::Checked => out.push_str(
+			"\tif (__builtin_add_overflow((int64_t)tape[at], delta, &value)) fail(\"the cell value overflowed\");\n")
+	}
+	if config.width != CellWidth::I64 {
+		match config.overflow {
+			Overflow::Wrapping => {},
+			Overflow::Saturating => {
+				let _ = writeln!(out, "\tif (value < 0) value = 0;\n\tif (value > {}) value = {};", max, max);
+			},
+			Overflow::Checked => {
+				let _ = writeln!(out, "\tif (value < 0 || value > {}) fail(\"the cell value overflowed\");", max);
+			}
+		}
+	}
+	out.push_str("\ttape[at] = (cell)value;\n}\n\n");
+
+	out.push_str("static inline void mul_add(ptrdiff_t to, int64_t factor) {\n\tint64_t delta;\n\n");
+	out.push_str("\tif (!tape[pos]) return;\n");
+	match config.overflow {
+		Overflow::Wrapping => out.push_str("\t__builtin_mul_overflow((int64_t)tape[pos], factor, &delta);\n"),
+		Overflow::Saturating => out.push_str("\tif (__builtin_mul_overflow((int64_t)tape[pos], factor, &delta))
+		delta = ((int64_t)tape[pos] < 0) != (factor < 0) ? INT64_MIN : INT64_MAX;
+"),
+		Overflow::Checked => out.push_str(
+			"\tif (__builtin_mul_overflow((int64_t)tape[pos], factor, &delta)) fail(\"the cell value overflowed\");\n")
+	}
+	out.push_str("\tadd(offset(to), delta);\n}\n\n");
+
+	out.push_str("static inline void output(void) {\n\tputchar((unsigned char)tape[pos]);\n}\n\n");
+
+	out.push_str("static inline void input(void) {\n\tint c;\n\n\tfflush(stdout);\n\tc = getchar();\n");
+	out.push_str("\tif (c != EOF) tape[pos] = (cell)c;\n");
+	match config.eof {
+		EofPolicy::Unchanged => {},
+		EofPolicy::Zero => out.push_str("\telse tape[pos] = 0;\n"),
+		EofPolicy::MinusOne => out.push_str("\telse tape[pos] = (cell)-1;\n"),
+		EofPolicy::Error => out.push_str("\telse fail(\"the input ran out\");\n")
+	}
+	out.push_str("}\n\n");
+
+	out.push_str("int main(void) {\n\ttape = calloc(len, sizeof(cell));\n\tif (!tape) fail(\"out of memory\");\n\n");
+}
+
+/* Rust */
+
+fn rust_prelude(out: &mut String, config: &TapeConfig) {
+	let cell = match config.width {
+		CellWidth::U8 => "u8",
+		CellWidth::U16 => "u16",
+		CellWidth::U32 => "u32",
+		CellWidth::I64 => "i64"
+	};
+
+	let _ = write!(out, "\
+#![allow(dead_code, unused_mut)]
+
+use std::io::{{self, Read, Write}};
+use std::process;
+
+type Cell = {};
+
+struct Tape {{
+	cells: Vec<Cell>,
+	pos: usize,
+	output: io::BufWriter<io::Stdout>
+}}
+
+fn fail(message: &str) -> ! {{
+	eprintln!(\"error: {{}}\", message);
+	process::exit({})
+}}
+
+impl Tape {{
+", cell, EXIT_RUNTIME);
+
+	out.push_str("\tfn offset(&mut self, n: isize) -> usize {\n");
+	out.push_str("\t\tlet len = self.cells.len() as isize;\n\t\tlet target = self.pos as isize + n;\n\n");
+	out.push_str("\t\tif target >= 0 && target < len { return target as usize; }\n");
+	if config.growable {
+		out.push_str("\t\tif target >= 0 {\n\t\t\tself.cells.resize(target as usize + 1, 0);\n\t\t\treturn target as usize;\n\t\t}\n");
+	}
+	if config.wrap_pointer {
+		out.push_str("\t\ttarget.rem_euclid(len) as usize\n\t}\n\n");
+	} else {
+		out.push_str("\t\tself.output.flush().unwrap();\n");
+		out.push_str("\t\tif target < 0 { fail(\"the pointer moved left of the first cell\") }\n");
+		out.push_str("\t\tfail(\"the pointer moved past the last cell\")\n\t}\n\n");
+	}
+
+	let (add, mul) = match config.overflow {
+		Overflow::Wrapping => ("Some(value.wrapping_add(delta))", "Some(value.wrapping_mul(factor))"),
+		Overflow::Saturating => ("Some(value.saturating_add(delta))", "Some(value.saturating_mul(factor))"),
+		Overflow::Checked => ("value.checked_add(delta)", "value.checked_mul(factor)")
+	};
+	let range = match (config.width, config.overflow) {
+		(CellWidth::I64, _) | (_, Overflow::Wrapping) => "",
+		(_, Overflow::Saturating) => ".map(|value| value.max(0).min(Cell::max_value() as i64))",
+		(_, Overflow::Checked) => ".filter(|&value| value >= 0 && value <= Cell::max_value() as i64)"
+	};
+	let _ = write!(out, "\tfn add(&mut self, at: usize, delta: i64) {{
+		let value = self.cells[at] as i64;
+		match {}{} {{
+			Some(value) => self.cells[at] = value as Cell,
+			None => {{
+				self.output.flush().unwrap();
+				fail(\"the cell value overflowed\")
+			}}
+		}}
+	}}
+
+	fn mul_add(&mut self, to: isize, factor: i64) {{
+		let value = self.cells[self.pos] as i64;
+		if value == 0 {{ return; }}
+		match {} {{
+			Some(delta) => {{
+				let at = self.offset(to);
+				self.add(at, delta)
+			}},
+			None => {{
+				self.output.flush().unwrap();
+				fail(\"the cell value overflowed\")
+			}}
+		}}
+	}}
+
+	fn output(&mut self) {{
+		self.output.write_all(&[self.cells[self.pos] as u8]).unwrap();
+	}}
+
+", add, range, mul);
+
+	out.push_str("\tfn input(&mut self) {\n\t\tlet mut buf = [0];\n\n\t\tself.output.flush().unwrap();\n");
+	out.push_str("\t\tmatch io::stdin().read(&mut buf) {\n\t\t\tOk(1) => self.cells[self.pos] = buf[0] as Cell,\n");
+	match config.eof {
+		EofPolicy::Unchanged => out.push_str("\t\t\t_ => {}\n"),
+		EofPolicy::Zero => out.push_str("\t\t\t_ => self.cells[self.pos] = 0\n"),
+		EofPolicy::MinusOne => out.push_str("\t\t\t_ => self.cells[self.pos] = (-1i64) as Cell\n"),
+		EofPolicy::Error => out.push_str("\t\t\t_ => fail(\"the input ran out\")\n")
+	}
+	out.push_str("\t\t}\n\t}\n}\n\n");
+
+	let _ = write!(out, "\
+fn main() {{
+	let mut t = Tape {{
+		cells: vec![0; {}],
+		pos: 0,
+		output: io::BufWriter::new(io::stdout())
+	}};
+
+", config.cells);
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use super::{transpile, Target};
+	use super::super::{ir, ParseBrainfuck, HELLOWORLD};
+	use super::super::config::{CellWidth, EofPolicy, Overflow, TapeConfig};
+
+	// a bit of everything the IR has to offer, though its loops stay loops on saturating cells
+	const SAMPLE: &[u8] = b",[->++>+<<]>[.>]<<[-]+[<]";
+
+	fn transpiled(source: &[u8], config: &TapeConfig, target: Target) -> String {
+		transpile(&ir::optimize(&source.parse().unwrap(), config.overflow), config, target)
+	}
+
+	fn dialect() -> TapeConfig {
+		TapeConfig {
+			cells: 1000,
+			width: CellWidth::U16,
+			overflow: Overflow::Saturating,
+			growable: true,
+			wrap_pointer: false,
+			eof: EofPolicy::Error
+		}
+	}
+
+	#[test]
+	fn transpiling_to_c() {
+		assert_eq!(transpiled(HELLOWORLD, &TapeConfig::classic(), Target::C), include_str!("golden/helloworld.c"));
+		assert_eq!(transpiled(SAMPLE, &dialect(), Target::C), include_str!("golden/sample.c"));
+	}
+
+	#[test]
+	fn transpiling_to_rust() {
+		assert_eq!(transpiled(HELLOWORLD, &TapeConfig::classic(), Target::Rust), include_str!("golden/helloworld.rs"));
+		assert_eq!(transpiled(SAMPLE, &dialect(), Target::Rust), include_str!("golden/sample.rs"));
+	}
+}
@@ -0,0 +1,349 @@
+use std::io::{Read, Write};
+use std::mem;
+use super::{Interpreter, ParseError, Position, RuntimeError};
+use super::ParseError::*;
+use super::config::Overflow;
+use super::ir::{self, Op};
+use self::Instruction::*;
+
+/* bytecode */
+
+// the optimized ops with loops flattened into jumps; the targets are the positions of the
+// matching jump, so instruction positions are the same as in the optimized ops
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Instruction {
+	Add(i64),
+	Move(isize),
+	SetZero,
+	MulAdd { offset: isize, factor: i64 },
+	Scan(isize),
+	Output,
+	Input,
+	// '[', which jumps to its ']' if the cell is zero
+	JumpIfZero(usize),
+	// ']', which jumps back to its '[' unless the cell is zero
+	JumpUnlessZero(usize)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Bytecode {
+	code: Vec<Instruction>,
+	// the instruction position in the source every instruction starts at, in increasing order
+	positions: Vec<usize>
+}
+
+impl Bytecode {
+	// compiles plain Brainfuck source in a single pass, without building a tree of loops on the way,
+	// so that there is no limit to how deeply they can be nested; like the optimized ops, the code
+	// only works the same as the source on tapes with the given overflow mode
+	pub fn compile(source: &[u8], overflow: Overflow) -> Result<Bytecode, ParseError> {
+		let mut bytecode = Bytecode { code: Vec::new(), positions: Vec::new() };
+		// the positions of the open loops' JumpIfZero and of their '[' in the source
+		let mut open: Vec<(usize, Position)> = Vec::new();
+		let mut position = Position { offset: 0, line: 1, column: 1 };
+		// the instruction position of the next instruction of the source
+		let mut next = 0;
+
+		for (offset, &c) in source.iter().enumerate() {
+			position.offset = offset;
+
+			match c {
+				b'>' => bytecode.push_move(1, next),
+				b'<' => bytecode.push_move(-1, next),
+				b'+' => bytecode.push_add(1, overflow, next),
+				b'-' => bytecode.push_add(-1, overflow, next),
+				b'.' => bytecode.push(Output, next),
+				b',' => bytecode.push(Input, next),
+				b'[' => {
+					open.push((bytecode.code.len(), position));
+					bytecode.push(JumpIfZero(0), next);
+				},
+				b']' => match open.pop() {
+					Some((start, _)) => bytecode.close_loop(start, overflow, next),
+					None => return Err(UnmatchedClose(']', position))
+				},
+				_ => {}
+			}
+
+			if b"><+-.,[]".contains(&c) { next += 1; }
+			position.advance(c);
+		}
+
+		match open.into_iter().next() {
+			Some((_, position)) => Err(UnmatchedOpen('[', position)),
+			None => Ok(bytecode)
+		}
+	}
+
+	pub fn code(&self) -> &[Instruction] { &self.code }
+
+	// the instruction position in the source of the instruction at `pc`
+	pub fn position(&self, pc: usize) -> usize { self.positions[pc] }
+
+	// the instruction starting at the given instruction position in the source, if any
+	pub fn instruction_at(&self, position: usize) -> Option<usize> { self.positions.binary_search(&position).ok() }
+
+	fn push(&mut self, instruction: Instruction, position: usize) {
+		self.code.push(instruction);
+		self.positions.push(position);
+	}
+
+	fn pop(&mut self) {
+		self.code.pop();
+		self.positions.pop();
+	}
+
+	fn push_add(&mut self, n: i64, overflow: Overflow, position: usize) {
+		if let Some(&mut Add(ref mut m)) = self.code.last_mut() {
+			if ir::folds(overflow, *m, n) {
+				*m += n;
+				if *m == 0 { self.pop(); }
+				return;
+			}
+		}
+		self.push(Add(n), position);
+	}
+
+	fn push_move(&mut self, n: isize, position: usize) {
+		if let Some(&mut Move(ref mut m)) = self.code.last_mut() {
+			*m += n;
+			if *m == 0 { self.pop(); }
+			return;
+		}
+		self.push(Move(n), position);
+	}
+
+	// replaces the loop starting at the given position with the ops the optimizer would use instead;
+	// only loops made of nothing but additions and moves can be replaced, and the ops replacing one
+	// start at the positions of its first instructions
+	fn close_loop(&mut self, start: usize, overflow: Overflow, position: usize) {
+		let body: Option<Vec<Op>> = self.code[start + 1..].iter().map(|instruction| match *instruction {
+			Add(n) => Some(Op::Add(n)),
+			Move(n) => Some(Op::Move(n)),
+			_ => None
+		}).collect();
+
+		if let Some(body) = body {
+			let replacement = ir::optimize_loop(body, overflow);
+			if !matches!(replacement[..], [Op::Loop(_)]) {
+				let first = self.positions[start];
+				self.code.truncate(start);
+				self.positions.truncate(start);
+				for (i, op) in replacement.into_iter().enumerate() {
+					self.push(match op {
+						Op::SetZero => SetZero,
+						Op::MulAdd { offset, factor } => MulAdd { offset: offset, factor: factor },
+						Op::Scan(n) => Scan(n),
+						_ => unreachable!()
+					}, first + i);
+				}
+				return;
+			}
+		}
+
+		let end = self.code.len();
+		let _ = mem::replace(&mut self.code[start], JumpIfZero(end));
+		self.push(JumpUnlessZero(start), position);
+	}
+}
+
+/* execution */
+
+impl<R: Read, W: Write> Interpreter<R, W> {
+	// errors are at the instruction positions of the source, like with run_unoptimized, but the steps
+	// are those of the bytecode, where a run of folded instructions or a replaced loop is a single one
+	pub fn run_bytecode(&mut self, bytecode: &Bytecode) -> Result<(), RuntimeError> {
+		self.run_bytecode_from(bytecode, 0)
+	}
+
+	// the loops need no stack, so running can continue from any instruction position
+	pub fn run_bytecode_from(&mut self, bytecode: &Bytecode, mut pc: usize) -> Result<(), RuntimeError> {
+		let code = &bytecode.code[..];
+
+		while pc < code.len() {
+			self.step().map_err(|kind| self.error(kind, bytecode.positions[pc]))?;
+
+			match code[pc] {
+				Add(n) => self.tape.add(n as i128),
+				Move(n) => self.tape.move_by(n),
+				SetZero => {
+					self.tape.set_cell(0);
+					Ok(())
+				},
+				MulAdd { offset, factor } => {
+					let value = self.tape.cell();
+					if value != 0 {
+						self.tape.offset(offset).and_then(|target| self.tape.add_at(target, value as i128 * factor as i128))
+					} else {
+						Ok(())
+					}
+				},
+				Scan(n) => {
+					let mut result = Ok(());
+					while result.is_ok() && self.tape.cell() != 0 {
+						result = self.tape.move_by(n).and_then(|_| self.step());
+					}
+					result
+				},
+				Output => self.output_byte(),
+				Input => self.input_byte(),
+				JumpIfZero(end) => {
+					if self.tape.cell() == 0 { pc = end; }
+					Ok(())
+				},
+				JumpUnlessZero(start) => {
+					if self.tape.cell() != 0 { pc = start; }
+					Ok(())
+				}
+			}.map_err(|kind| self.error(kind, bytecode.positions[pc]))?;
+
+			pc += 1;
+		}
+
+		Ok(())
+	}
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+	use super::Bytecode;
+	use super::Instruction::*;
+	use super::super::{ir, Interpreter, ParseBrainfuck, Position, Program, RuntimeError, FIZZBUZZ, HELLOWORLD};
+	use super::super::budget::Budget;
+	use super::super::config::{CellWidth, Overflow, TapeConfig};
+	use super::super::ParseError::*;
+	use super::super::RuntimeErrorKind::PointerUnderflow;
+
+	fn interpreter() -> Interpreter<io::Empty, Vec<u8>> {
+		Interpreter::new(io::empty(), Vec::new(), TapeConfig { cells: 100, ..TapeConfig::classic() })
+	}
+
+	fn assert_equivalent(source: &[u8]) {
+		let mut optimized = interpreter();
+		let mut compiled = interpreter();
+		let optimized_result = optimized.run_ir(&ir::optimize(&source.parse().unwrap(), Overflow::Wrapping));
+		let compiled_result = compiled.run_bytecode(&Bytecode::compile(source, Overflow::Wrapping).unwrap());
+
+		// only the positions differ, as the optimized ops have their own
+		assert_eq!(compiled_result.map_err(|e| (e.kind, e.steps)), optimized_result.map_err(|e| (e.kind, e.steps)));
+		assert_eq!(compiled.tape, optimized.tape);
+		assert_eq!(compiled.output(), optimized.output());
+	}
+
+	#[test]
+	fn compiling() {
+		assert_eq!(Bytecode::compile(b"+++[->+<]>[>>-]<.", Overflow::Wrapping).unwrap().code(), &[
+			Add(3),
+			MulAdd { offset: 1, factor: 1 },
+			SetZero,
+			Move(1),
+			JumpIfZero(7),
+			Move(2),
+			Add(-1),
+			JumpUnlessZero(4),
+			Move(-1),
+			Output
+		]);
+		assert_eq!(Bytecode::compile(b"[][-][<]", Overflow::Wrapping).unwrap().code(), &[JumpIfZero(1), JumpUnlessZero(0), SetZero, Scan(-1)]);
+	}
+
+	#[test]
+	fn unmatched_brackets() {
+		assert_eq!(Bytecode::compile(b"+\n]", Overflow::Wrapping), Err(UnmatchedClose(']', Position { offset: 2, line: 2, column: 1 })));
+		assert_eq!(Bytecode::compile(b"[[]", Overflow::Wrapping), Err(UnmatchedOpen('[', Position { offset: 0, line: 1, column: 1 })));
+	}
+
+	#[test]
+	fn equivalence_with_optimized_ops() {
+		assert_equivalent(HELLOWORLD);
+		assert_equivalent(FIZZBUZZ);
+		assert_equivalent(b"++[>+++[>++<-]<-]>>[>]+[[-]>]");
+		// errors happen at the same positions after the same number of steps
+		assert_equivalent(b"+[-]+[>+<-]<");
+		assert_equivalent(b"+[>+]");
+		assert_equivalent(b"+[<<+>>-]");
+	}
+
+	#[test]
+	fn overflow_modes() {
+		assert_eq!(Bytecode::compile(b"++-[-][>>]", Overflow::Saturating).unwrap().code(), &[
+			Add(2),
+			Add(-1),
+			JumpIfZero(4),
+			Add(-1),
+			JumpUnlessZero(2),
+			Scan(2)
+		]);
+
+		// programs whose cells hit their limits, which only wrapping cells may ignore
+		let programs: &[&[u8]] = &[
+			b"-+.>+[+]",
+			b">++++++++++++++++[<++++++++++++++++>-]<-[->+<]>+-.",
+			b">++++++++++++++++[<++++++++++++++++>-]<-----<+++[->++++>+<--<]>.>.",
+			HELLOWORLD
+		];
+		for &overflow in &[Overflow::Wrapping, Overflow::Saturating, Overflow::Checked] {
+			let config = TapeConfig { cells: 16, width: CellWidth::U8, overflow: overflow, ..TapeConfig::default() };
+
+			for (i, &source) in programs.iter().enumerate() {
+				let mut naive = Interpreter::new(io::empty(), Vec::new(), config);
+				let mut compiled = Interpreter::new(io::empty(), Vec::new(), config);
+				naive.set_budget(Budget { max_steps: Some(100_000), ..Budget::unlimited() });
+				compiled.set_budget(Budget { max_steps: Some(100_000), ..Budget::unlimited() });
+
+				let naive_result = naive.run_unoptimized(&Program::parse(source).unwrap());
+				let compiled_result = compiled.run_bytecode(&Bytecode::compile(source, overflow).unwrap());
+				assert_eq!(naive_result.map_err(|e| e.kind), compiled_result.map_err(|e| e.kind), "{:?} {}", overflow, i);
+				if naive_result.is_ok() {
+					assert_eq!(naive.output(), compiled.output(), "{:?} {}", overflow, i);
+					assert_eq!(naive.tape.array, compiled.tape.array, "{:?} {}", overflow, i);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn deeply_nested_loops() {
+		let depth = 100_000;
+		let mut source = vec![b'+'; 1];
+		source.extend(vec![b'['; depth]);
+		source.push(b'-');
+		source.extend(vec![b']'; depth]);
+		source.push(b'+');
+
+		let mut interpreter = interpreter();
+		interpreter.run_bytecode(&Bytecode::compile(&source, Overflow::Wrapping).unwrap()).unwrap();
+
+		assert_eq!(interpreter.tape.array[0], 1);
+		// the innermost loop becomes a single SetZero
+		assert_eq!(interpreter.tape.steps, 2 * depth as u64 + 1);
+	}
+
+	#[test]
+	fn error_positions() {
+		let source = b"+++++ +++++ <";
+		let mut compiled = interpreter();
+		let mut naive = interpreter();
+		let error = compiled.run_bytecode(&Bytecode::compile(source, Overflow::Wrapping).unwrap()).unwrap_err();
+
+		// the position is in the source, while the steps are the bytecode's
+		assert_eq!(error, RuntimeError { kind: PointerUnderflow, position: 10, steps: 2 });
+		assert_eq!(naive.run_unoptimized(&Program::parse(source).unwrap()).unwrap_err().position, 10);
+
+		let bytecode = Bytecode::compile(b"++ [->+<] . [>]", Overflow::Wrapping).unwrap();
+		assert_eq!((0..bytecode.code().len()).map(|pc| bytecode.position(pc)).collect::<Vec<_>>(), vec![0, 2, 3, 8, 9]);
+		assert_eq!(bytecode.instruction_at(8), Some(3));
+		assert_eq!(bytecode.instruction_at(4), None);
+	}
+
+	#[test]
+	fn scans_count_steps() {
+		let mut interpreter = interpreter();
+		let error = interpreter.run_bytecode(&Bytecode::compile(b"+[<]", Overflow::Wrapping).unwrap()).unwrap_err();
+
+		assert_eq!(error, RuntimeError { kind: PointerUnderflow, position: 1, steps: 2 });
+	}
+}
@@ -3,11 +3,18 @@
 use self::Rank::*;
 use self::Suit::*;
 use self::CardsError::*;
+use std::cmp::{self, Reverse};
+use std::error::Error;
 use std::fmt;
-use rand::{thread_rng, Rng};
+use std::str::FromStr;
+use rand::{thread_rng, Rng, SeedableRng};
+use rand::isaac::Isaac64Rng;
 
 /* objects */
 
+// the ranks of the cards with a suit, from the lowest; a joker can stand for any of them
+const NATURAL_RANKS: [Rank; 13] = [Two, Three, Four, Five, Six, Seven, Eight, Nine, Ten, Jack, Queen, King, Ace];
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum Rank {
 	Two = 2,
@@ -27,27 +34,31 @@ pub enum Rank {
 }
 
 impl Rank {
-	pub fn new_safe(rank: &str) -> Result<Rank, CardsError> {
+	// accepts the names of the ranks as well as their short forms, in any case
+	pub fn try_new(rank: &str) -> Result<Rank, CardsError> {
 		match &*rank.to_lowercase() {
-			"ace" => Ok(Ace),
-			"two" => Ok(Two),
-			"three" => Ok(Three),
-			"four" => Ok(Four),
-			"five" => Ok(Five),
-			"six" => Ok(Six),
-			"seven" => Ok(Seven),
-			"eight" => Ok(Eight),
-			"nine" => Ok(Nine),
-			"ten" => Ok(Ten),
-			"jack" => Ok(Jack),
-			"queen" => Ok(Queen),
-			"king" => Ok(King),
-			"joker" => Ok(Joker),
-			_ => Err(InvalidRank)
+			"ace" | "a" => Ok(Ace),
+			"two" | "2" => Ok(Two),
+			"three" | "3" => Ok(Three),
+			"four" | "4" => Ok(Four),
+			"five" | "5" => Ok(Five),
+			"six" | "6" => Ok(Six),
+			"seven" | "7" => Ok(Seven),
+			"eight" | "8" => Ok(Eight),
+			"nine" | "9" => Ok(Nine),
+			"ten" | "10" | "t" => Ok(Ten),
+			"jack" | "j" => Ok(Jack),
+			"queen" | "q" => Ok(Queen),
+			"king" | "k" => Ok(King),
+			"joker" | "jkr" => Ok(Joker),
+			_ => Err(InvalidRank(rank.to_owned()))
 		}
 	}
 
-	pub fn new(rank: &str) -> Rank { Rank::new_safe(rank).unwrap() }
+	pub fn new(rank: &str) -> Rank { Rank::try_new(rank).unwrap() }
+
+	#[deprecated(note = "use Rank::try_new")]
+	pub fn new_safe(rank: &str) -> Result<Rank, CardsError> { Rank::try_new(rank) }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -59,17 +70,21 @@ pub enum Suit {
 }
 
 impl Suit {
-	pub fn new_safe(suit: &str) -> Result<Suit, CardsError> {
+	// accepts the names of the suits as well as their initials and symbols, in any case
+	pub fn try_new(suit: &str) -> Result<Suit, CardsError> {
 		match &*suit.to_lowercase() {
-			"spades" => Ok(Spades),
-			"clubs" => Ok(Clubs),
-			"diamonds" => Ok(Diamonds),
-			"hearts" => Ok(Hearts),
-			_ => Err(InvalidSuit)
+			"spades" | "s" | "♠" | "♤" => Ok(Spades),
+			"clubs" | "c" | "♣" | "♧" => Ok(Clubs),
+			"diamonds" | "d" | "♦" | "♢" => Ok(Diamonds),
+			"hearts" | "h" | "♥" | "♡" => Ok(Hearts),
+			_ => Err(InvalidSuit(suit.to_owned()))
 		}
 	}
 
-	pub fn new(suit: &str) -> Suit { Suit::new_safe(suit).unwrap() }
+	pub fn new(suit: &str) -> Suit { Suit::try_new(suit).unwrap() }
+
+	#[deprecated(note = "use Suit::try_new")]
+	pub fn new_safe(suit: &str) -> Result<Suit, CardsError> { Suit::try_new(suit) }
 }
 
 #[derive(Debug, PartialEq)]
@@ -87,58 +102,337 @@ impl Card {
 	}
 }
 
+#[derive(Debug)]
 pub struct Cards { pub cards: Vec<Card> }
 
 pub type Deck = Cards;
 pub type Hand = Cards;
 
 impl Deck {
-	pub fn new() -> Deck {
-		let mut cards = Vec::with_capacity(53);
-		for &suit in [Spades, Hearts, Diamonds, Clubs].iter() {
-			for &rank in [Two, Three, Four, Five, Six, Seven, Eight, Nine, Ten, Jack, Queen, King, Ace].iter() {
-				cards.push(Card::new(rank, Some(suit)));
-			}
-		}
+	// the 52-card French deck
+	pub fn new() -> Deck { DeckBuilder::new().build() }
 
-		Deck { cards: cards }
+	pub fn new_with_joker() -> Deck { DeckBuilder::new().jokers(1).build() }
+
+	// the 32 cards from sevens to aces
+	pub fn piquet() -> Deck { DeckBuilder::new().ranks(Seven, Ace).build() }
+
+	// the 24 cards from nines to aces
+	pub fn euchre() -> Deck { DeckBuilder::new().ranks(Nine, Ace).build() }
+
+	// two copies of the euchre deck, 48 cards
+	pub fn pinochle() -> Deck { DeckBuilder::new().ranks(Nine, Ace).copies(2).build() }
+
+	// the given number of French decks shuffled together, like the shoes of blackjack
+	pub fn shoe(decks: usize) -> Deck { DeckBuilder::new().copies(decks).build() }
+
+	// shuffles with a random seed and returns it, so that the shuffle can be replayed with shuffle_seeded
+	pub fn shuffle(&mut self) -> u64 {
+		let seed = thread_rng().gen();
+		self.shuffle_seeded(seed);
+		seed
 	}
 
-	pub fn new_with_joker() -> Deck {
-		let mut deck = Deck::new();
-		deck.cards.push(Card::new(Joker, None));
-		deck
+	// the same seed always gives the same order, on every platform
+	pub fn shuffle_seeded(&mut self, seed: u64) {
+		self.shuffle_with(&mut Isaac64Rng::from_seed(&[seed]));
 	}
 
-	pub fn shuffle(&mut self) {
-		let mut rng = thread_rng();
-		rng.shuffle(&mut self.cards);
+	// Fisher–Yates with the indices drawn as u64 rather than usize, which would draw differently on
+	// 32-bit platforms
+	pub fn shuffle_with<R: Rng>(&mut self, rng: &mut R) {
+		for i in (1..self.cards.len()).rev() {
+			let j = rng.gen_range(0, i as u64 + 1) as usize;
+			self.cards.swap(i, j);
+		}
 	}
 
 	pub fn size(&self) -> usize { self.cards.len() }
 
 	pub fn draw1(&mut self) -> Result<Card, CardsError> {
-		if self.size() == 0 {
-			Err(EmptyDeck)
+		self.cards.pop().ok_or(NotEnoughCards { requested: 1, available: 0 })
+	}
+
+	// draws either all of the cards or, if there aren't enough of them, none
+	pub fn draw(&mut self, number: usize) -> Result<Vec<Card>, CardsError> {
+		self.ensure(number)?;
+		let rest = self.size() - number;
+
+		Ok(self.cards.drain(rest..).rev().collect())
+	}
+
+	// deals the given number of cards to every player, one at a time, like at a table; if there aren't
+	// enough cards for everyone, nothing is dealt
+	pub fn deal(&mut self, players: usize, cards: usize) -> Result<Vec<Hand>, CardsError> {
+		// there can't be more players at the table than there are cards, even if none are dealt
+		let requested = players.checked_mul(cards).filter(|_| players <= self.size());
+		self.ensure(requested.ok_or(InvalidDeal { players: players, cards: cards })?)?;
+		let mut hands: Vec<Hand> = (0..players).map(|_| Hand { cards: Vec::with_capacity(cards) }).collect();
+
+		for _ in 0..cards {
+			for hand in hands.iter_mut() { hand.cards.push(self.draw1()?) }
+		}
+
+		Ok(hands)
+	}
+
+	fn ensure(&self, requested: usize) -> Result<(), CardsError> {
+		if requested > self.size() {
+			Err(NotEnoughCards { requested: requested, available: self.size() })
 		} else {
-			Ok(self.cards.pop().unwrap())
+			Ok(())
+		}
+	}
+}
+
+// the cards of every suit in a range of ranks, repeated a number of times, and some jokers after them
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeckBuilder {
+	lowest: Rank,
+	highest: Rank,
+	copies: usize,
+	jokers: usize
+}
+
+impl DeckBuilder {
+	// the 52-card French deck
+	pub fn new() -> DeckBuilder {
+		DeckBuilder {
+			lowest: Two,
+			highest: Ace,
+			copies: 1,
+			jokers: 0
+		}
+	}
+
+	// jokers don't count as a rank here, only as jokers; the bounds may be given either way round
+	pub fn ranks(mut self, lowest: Rank, highest: Rank) -> DeckBuilder {
+		self.lowest = cmp::min(lowest, highest);
+		self.highest = cmp::max(lowest, highest);
+		self
+	}
+
+	pub fn copies(mut self, copies: usize) -> DeckBuilder {
+		self.copies = copies;
+		self
+	}
+
+	pub fn jokers(mut self, jokers: usize) -> DeckBuilder {
+		self.jokers = jokers;
+		self
+	}
+
+	pub fn build(&self) -> Deck {
+		let ranks: Vec<Rank> = NATURAL_RANKS.iter().cloned()
+			.filter(|&rank| rank >= self.lowest && rank <= self.highest)
+			.collect();
+		let mut cards = Vec::with_capacity(ranks.len() * 4 * self.copies + self.jokers);
+
+		for _ in 0..self.copies {
+			for &suit in [Spades, Hearts, Diamonds, Clubs].iter() {
+				for &rank in ranks.iter() {
+					cards.push(Card::new(rank, Some(suit)));
+				}
+			}
+		}
+		for _ in 0..self.jokers {
+			cards.push(Card::new(Joker, None));
 		}
+
+		Deck { cards: cards }
 	}
+}
 
-	pub fn draw(&mut self, number: usize) -> Vec<Card> {
-		let mut drawn = Vec::with_capacity(number);
+impl Default for DeckBuilder {
+	fn default() -> DeckBuilder { DeckBuilder::new() }
+}
+
+// a deal along with the seed of its shuffle, which is enough to replay it
+pub struct Deal {
+	pub seed: u64,
+	pub hands: Vec<Hand>
+}
+
+impl Deal {
+	pub fn new(deck: Deck, players: usize, cards: usize) -> Result<Deal, CardsError> {
+		Deal::replay(deck, thread_rng().gen(), players, cards)
+	}
 
-		for _ in 0..number { drawn.push(self.draw1().unwrap()) }
+	// the deck has to be the same one the deal was made with
+	pub fn replay(mut deck: Deck, seed: u64, players: usize, cards: usize) -> Result<Deal, CardsError> {
+		deck.shuffle_seeded(seed);
 
-		drawn
+		Ok(Deal {
+			seed: seed,
+			hands: deck.deal(players, cards)?
+		})
 	}
 }
 
-#[derive(Debug)]
+/* evaluating */
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Category {
+	HighCard,
+	OnePair,
+	TwoPair,
+	ThreeOfAKind,
+	Straight,
+	Flush,
+	FullHouse,
+	FourOfAKind,
+	StraightFlush,
+	RoyalFlush,
+	// only possible with a joker
+	FiveOfAKind
+}
+
+// hands are compared by their category first, then by the ranks of their cards in the order they
+// matter: the largest group first (higher ranks first among groups of the same size), and the
+// highest card of a straight first, so that an ace-to-five straight ends with its ace
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct HandRank {
+	pub category: Category,
+	pub ranks: [Rank; 5]
+}
+
+impl Hand {
+	// the best hand made of five of the cards, with jokers standing for any card
+	pub fn evaluate(&self) -> Result<HandRank, CardsError> {
+		if self.size() < 5 {
+			return Err(TooFewCards { needed: 5, got: self.size() });
+		}
+
+		let cards: Vec<(Rank, Option<Suit>)> = self.cards.iter().map(|card| (card.rank, card.suit)).collect();
+		let mut best = None;
+		// the indices of the five cards, in increasing order
+		let mut chosen = [0, 1, 2, 3, 4];
+		loop {
+			let hand = [cards[chosen[0]], cards[chosen[1]], cards[chosen[2]], cards[chosen[3]], cards[chosen[4]]];
+			best = best.max(Some(evaluate5(hand, 0)));
+
+			// the next combination: bump the last index that can still move and reset the ones after it
+			match (0..5).rev().find(|&i| chosen[i] < cards.len() - 5 + i) {
+				Some(i) => {
+					chosen[i] += 1;
+					for j in i + 1..5 { chosen[j] = chosen[j - 1] + 1; }
+				},
+				None => return Ok(best.unwrap())
+			}
+		}
+	}
+}
+
+// jokers are replaced one by one, with ranks from `lowest` up; the order they're replaced in doesn't
+// matter, so every later one can be given a rank at least as high as the one before
+fn evaluate5(mut hand: [(Rank, Option<Suit>); 5], lowest: usize) -> HandRank {
+	if let Some(joker) = hand.iter().position(|&(rank, _)| rank == Joker) {
+		// the joker is best off matching the suit of the others, in case they make a flush
+		let suit = hand.iter().filter(|&&(rank, _)| rank != Joker).map(|&(_, suit)| suit).next().unwrap_or(Some(Spades));
+
+		return (lowest..NATURAL_RANKS.len()).map(|i| {
+			hand[joker] = (NATURAL_RANKS[i], suit);
+			evaluate5(hand, i)
+		}).max().unwrap();
+	}
+
+	let mut counts = [0u8; 15];
+	for &(rank, _) in hand.iter() { counts[rank as usize] += 1; }
+
+	let mut ranks = [hand[0].0, hand[1].0, hand[2].0, hand[3].0, hand[4].0];
+	ranks.sort_by_key(|&rank| (Reverse(counts[rank as usize]), Reverse(rank)));
+
+	let largest = counts[ranks[0] as usize];
+	let second = if largest < 5 { counts[ranks[largest as usize] as usize] } else { 0 };
+	let flush = hand.iter().all(|&(_, suit)| suit == hand[0].1);
+	let wheel = ranks == [Ace, Five, Four, Three, Two];
+	let straight = largest == 1 && (ranks[0] as u8 - ranks[4] as u8 == 4 || wheel);
+	if wheel { ranks = [Five, Four, Three, Two, Ace]; }
+
+	let category = match (largest, second) {
+		(5, _) => Category::FiveOfAKind,
+		(4, _) => Category::FourOfAKind,
+		(3, 2) => Category::FullHouse,
+		(3, _) => Category::ThreeOfAKind,
+		(2, 2) => Category::TwoPair,
+		(2, _) => Category::OnePair,
+		_ if straight && flush && ranks[0] == Ace => Category::RoyalFlush,
+		_ if straight && flush => Category::StraightFlush,
+		_ if flush => Category::Flush,
+		_ if straight => Category::Straight,
+		_ => Category::HighCard
+	};
+
+	HandRank {
+		category: category,
+		ranks: ranks
+	}
+}
+
+// the invalid ranks and suits are kept as they were given
+#[derive(Debug, PartialEq)]
 pub enum CardsError {
-	InvalidRank,
-	InvalidSuit,
-	EmptyDeck
+	InvalidRank(String),
+	InvalidSuit(String),
+	InvalidCard(String),
+	NotEnoughCards { requested: usize, available: usize },
+	// more players than cards in the deck, or more cards than can be counted
+	InvalidDeal { players: usize, cards: usize },
+	// a hand too small to be evaluated
+	TooFewCards { needed: usize, got: usize }
+}
+
+impl Error for CardsError {}
+
+/* parsing */
+
+// "A♠", "Ah", "10d", "Td", "Jkr" or "ace of spades"
+impl FromStr for Card {
+	type Err = CardsError;
+
+	fn from_str(card: &str) -> Result<Card, CardsError> {
+		let card = card.trim();
+		let words: Vec<&str> = card.split_whitespace().collect();
+
+		let (rank, suit) = match words.len() {
+			3 if words[1].to_lowercase() == "of" => (words[0], Some(words[2])),
+			1 => match Rank::try_new(card) {
+				// only jokers are written without a suit
+				Ok(Joker) => ("joker", None),
+				_ => match card.char_indices().last() {
+					Some((last, _)) if last > 0 => (&card[..last], Some(&card[last..])),
+					_ => return Err(InvalidCard(card.to_owned()))
+				}
+			},
+			_ => return Err(InvalidCard(card.to_owned()))
+		};
+
+		match (Rank::try_new(rank)?, suit) {
+			(Joker, None) => Ok(Card::new(Joker, None)),
+			(Joker, Some(_)) | (_, None) => Err(InvalidCard(card.to_owned())),
+			(rank, Some(suit)) => Ok(Card::new(rank, Some(Suit::try_new(suit)?)))
+		}
+	}
+}
+
+// cards separated by commas or whitespace, like "A♠, 10♥", "Ah Td Jkr" or "ace of spades, two of clubs"
+impl FromStr for Cards {
+	type Err = CardsError;
+
+	fn from_str(cards: &str) -> Result<Cards, CardsError> {
+		let words: Vec<&str> = cards.split(|c: char| c == ',' || c.is_whitespace()).filter(|w| !w.is_empty()).collect();
+		let mut parsed = Vec::new();
+
+		let mut i = 0;
+		while i < words.len() {
+			// the long form takes three words
+			let length = if i + 2 < words.len() && words[i + 1].to_lowercase() == "of" { 3 } else { 1 };
+			parsed.push(words[i..i + length].join(" ").parse()?);
+			i += length;
+		}
+
+		Ok(Cards { cards: parsed })
+	}
 }
 
 /* displaying */
@@ -185,6 +479,39 @@ impl fmt::Display for Card {
 	}
 }
 
+impl fmt::Display for CardsError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			InvalidRank(ref rank) => write!(f, "invalid rank: '{}'", rank),
+			InvalidSuit(ref suit) => write!(f, "invalid suit: '{}'", suit),
+			InvalidCard(ref card) => write!(f, "invalid card: '{}'", card),
+			NotEnoughCards { requested, available } => {
+				write!(f, "unable to draw {} card(s), as only {} are left", requested, available)
+			},
+			InvalidDeal { players, cards } => write!(f, "unable to deal {} card(s) to each of {} players", cards, players),
+			TooFewCards { needed, got } => write!(f, "a hand needs at least {} card(s), but only has {}", needed, got)
+		}
+	}
+}
+
+impl fmt::Display for Category {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", match *self {
+			Category::HighCard => "high card",
+			Category::OnePair => "one pair",
+			Category::TwoPair => "two pair",
+			Category::ThreeOfAKind => "three of a kind",
+			Category::Straight => "straight",
+			Category::Flush => "flush",
+			Category::FullHouse => "full house",
+			Category::FourOfAKind => "four of a kind",
+			Category::StraightFlush => "straight flush",
+			Category::RoyalFlush => "royal flush",
+			Category::FiveOfAKind => "five of a kind"
+		})
+	}
+}
+
 impl fmt::Display for Deck {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		for (count, card) in self.cards.iter().enumerate() {
@@ -193,4 +520,237 @@ impl fmt::Display for Deck {
 		}
 		write!(f, "")
 	}
-}
\ No newline at end of file
+}
+
+/* tests */
+
+#[cfg(test)]
+mod tests {
+	use rand::{SeedableRng, XorShiftRng};
+	use super::{Card, Category, Deal, Deck, DeckBuilder, Hand, HandRank, Rank, Suit};
+	use super::CardsError::*;
+	use super::Rank::*;
+	use super::Suit::*;
+
+	// jokers are given as a rank without a suit
+	fn hand(cards: &[(Rank, Option<Suit>)]) -> Hand {
+		Hand { cards: cards.iter().map(|&(rank, suit)| Card::new(rank, suit)).collect() }
+	}
+
+	fn category(cards: &[(Rank, Option<Suit>)]) -> Category {
+		hand(cards).evaluate().unwrap().category
+	}
+
+	#[test]
+	fn categories() {
+		let s = Some(Spades);
+		let h = Some(Hearts);
+		let d = Some(Diamonds);
+		let c = Some(Clubs);
+
+		assert_eq!(category(&[(Two, s), (Five, h), (Nine, d), (Jack, c), (King, s)]), Category::HighCard);
+		assert_eq!(category(&[(Two, s), (Two, h), (Nine, d), (Jack, c), (King, s)]), Category::OnePair);
+		assert_eq!(category(&[(Two, s), (Two, h), (Nine, d), (Nine, c), (King, s)]), Category::TwoPair);
+		assert_eq!(category(&[(Two, s), (Two, h), (Two, d), (Nine, c), (King, s)]), Category::ThreeOfAKind);
+		assert_eq!(category(&[(Ace, s), (Two, h), (Three, d), (Four, c), (Five, s)]), Category::Straight);
+		assert_eq!(category(&[(Ten, s), (Jack, h), (Queen, d), (King, c), (Ace, s)]), Category::Straight);
+		assert_eq!(category(&[(Jack, s), (Queen, h), (King, d), (Ace, c), (Two, s)]), Category::HighCard);
+		assert_eq!(category(&[(Two, h), (Five, h), (Nine, h), (Jack, h), (King, h)]), Category::Flush);
+		assert_eq!(category(&[(Two, s), (Two, h), (Two, d), (King, c), (King, s)]), Category::FullHouse);
+		assert_eq!(category(&[(Two, s), (Two, h), (Two, d), (Two, c), (King, s)]), Category::FourOfAKind);
+		assert_eq!(category(&[(Six, d), (Two, d), (Three, d), (Four, d), (Five, d)]), Category::StraightFlush);
+		assert_eq!(category(&[(Ten, c), (Jack, c), (Queen, c), (King, c), (Ace, c)]), Category::RoyalFlush);
+	}
+
+	#[test]
+	fn kickers() {
+		let s = Some(Spades);
+		let h = Some(Hearts);
+		let d = Some(Diamonds);
+		let c = Some(Clubs);
+
+		let kings = hand(&[(King, s), (King, h), (Two, d), (Three, c), (Four, s)]).evaluate().unwrap();
+		let kings_with_ace = hand(&[(King, d), (King, c), (Two, s), (Three, h), (Ace, s)]).evaluate().unwrap();
+		let queens = hand(&[(Queen, s), (Queen, h), (Ace, d), (King, c), (Jack, s)]).evaluate().unwrap();
+		assert!(kings < kings_with_ace && queens < kings);
+		assert_eq!(kings_with_ace.ranks, [King, King, Ace, Three, Two]);
+
+		// suits never break ties
+		let other_kings = hand(&[(King, d), (King, c), (Two, h), (Three, d), (Four, c)]).evaluate().unwrap();
+		assert_eq!(kings, other_kings);
+
+		// the ace of an ace-to-five straight is low
+		let wheel = hand(&[(Ace, s), (Two, h), (Three, d), (Four, c), (Five, s)]).evaluate().unwrap();
+		let six_high = hand(&[(Six, s), (Two, h), (Three, d), (Four, c), (Five, s)]).evaluate().unwrap();
+		assert!(wheel < six_high);
+		assert_eq!(wheel.ranks, [Five, Four, Three, Two, Ace]);
+
+		let full_house = hand(&[(Two, s), (Two, h), (Two, d), (Ace, c), (Ace, s)]).evaluate().unwrap();
+		let better_full_house = hand(&[(Three, s), (Three, h), (Three, d), (King, c), (King, s)]).evaluate().unwrap();
+		assert!(full_house < better_full_house);
+	}
+
+	#[test]
+	fn best_of_seven() {
+		let s = Some(Spades);
+		let h = Some(Hearts);
+		let d = Some(Diamonds);
+		let c = Some(Clubs);
+
+		let flush = hand(&[(Two, h), (Nine, h), (Four, s), (Jack, h), (Four, d), (King, h), (Five, h)]).evaluate().unwrap();
+		assert_eq!(flush, HandRank { category: Category::Flush, ranks: [King, Jack, Nine, Five, Two] });
+
+		// the best two pairs, with the best kicker left
+		let two_pair = hand(&[(Two, s), (Two, h), (Nine, d), (Nine, c), (King, s), (King, d), (Three, c)]).evaluate().unwrap();
+		assert_eq!(two_pair.ranks, [King, King, Nine, Nine, Three]);
+
+		// there is no poker hand without five cards
+		assert_eq!(hand(&[(Ace, s), (Ace, h), (Ace, d), (Ace, c)]).evaluate(), Err(TooFewCards { needed: 5, got: 4 }));
+		assert_eq!(hand(&[]).evaluate().unwrap_err().to_string(), "a hand needs at least 5 card(s), but only has 0");
+	}
+
+	#[test]
+	fn jokers() {
+		let s = Some(Spades);
+		let h = Some(Hearts);
+		let d = Some(Diamonds);
+
+		assert_eq!(category(&[(Joker, None), (Ten, s), (Jack, s), (Queen, s), (King, s)]), Category::RoyalFlush);
+		assert_eq!(category(&[(Joker, None), (Ace, s), (Ace, h), (Ace, d), (Ace, Some(Clubs))]), Category::FiveOfAKind);
+		assert_eq!(category(&[(Joker, None), (Joker, None), (Two, s), (Seven, h), (Nine, d)]), Category::ThreeOfAKind);
+		assert_eq!(hand(&[(Joker, None), (Two, s), (Two, h), (Nine, d), (Nine, s)]).evaluate().unwrap().ranks,
+			[Nine, Nine, Nine, Two, Two]);
+		assert_eq!(category(&[(Joker, None); 5]), Category::FiveOfAKind);
+	}
+
+	#[test]
+	fn seeded_shuffles() {
+		let mut deck = Deck::new();
+		deck.shuffle_seeded(7);
+		let mut again = Deck::new();
+		again.shuffle_seeded(7);
+		assert_eq!(deck.to_string(), again.to_string());
+
+		let mut other = Deck::new();
+		other.shuffle_seeded(8);
+		assert!(deck.to_string() != other.to_string());
+		// the order is the same everywhere, as the indices are drawn as u64 whatever the size of usize
+		assert_eq!(Hand { cards: deck.draw(5).unwrap() }.to_string(), "4♣, 6♥, 4♥, 10♣, 7♣");
+
+		// a random shuffle can be replayed from its seed
+		let mut random = Deck::new();
+		let seed = random.shuffle();
+		let mut replayed = Deck::new();
+		replayed.shuffle_seeded(seed);
+		assert_eq!(random.to_string(), replayed.to_string());
+
+		let mut first = Deck::new_with_joker();
+		first.shuffle_with(&mut XorShiftRng::from_seed([1, 2, 3, 4]));
+		let mut second = Deck::new_with_joker();
+		second.shuffle_with(&mut XorShiftRng::from_seed([1, 2, 3, 4]));
+		assert_eq!(first.to_string(), second.to_string());
+	}
+
+	#[test]
+	fn dealing() {
+		let mut deck = Deck::new();
+		let hands = deck.deal(4, 5).unwrap();
+		assert_eq!(hands.len(), 4);
+		assert!(hands.iter().all(|hand| hand.size() == 5));
+		assert_eq!(deck.size(), 32);
+		// the cards go around the table, from the top of the deck
+		assert_eq!(hands[0].to_string(), "A♣, 10♣, 6♣, 2♣, J♦");
+
+		let deal = Deal::new(Deck::new(), 2, 7).unwrap();
+		let replayed = Deal::replay(Deck::new(), deal.seed, 2, 7).unwrap();
+		for (hand, again) in deal.hands.iter().zip(replayed.hands.iter()) {
+			assert_eq!(hand.to_string(), again.to_string());
+		}
+	}
+
+	#[test]
+	fn running_out_of_cards() {
+		let mut deck = Deck::new();
+		assert_eq!(deck.draw(50).unwrap().len(), 50);
+		// nothing is drawn if there aren't enough cards
+		assert_eq!(deck.draw(3).unwrap_err(), NotEnoughCards { requested: 3, available: 2 });
+		assert_eq!(deck.size(), 2);
+		assert_eq!(deck.deal(2, 2).unwrap_err().to_string(), "unable to draw 4 card(s), as only 2 are left");
+		assert_eq!(deck.deal(usize::MAX, 2).unwrap_err(), InvalidDeal { players: usize::MAX, cards: 2 });
+		assert_eq!(deck.deal(usize::MAX, 0).unwrap_err().to_string(), format!("unable to deal 0 card(s) to each of {} players", usize::MAX));
+		assert_eq!(deck.deal(2, usize::MAX / 2 + 1).unwrap_err(), InvalidDeal { players: 2, cards: usize::MAX / 2 + 1 });
+		assert_eq!(deck.deal(2, 0).unwrap().len(), 2);
+		assert_eq!(deck.draw(2).unwrap().len(), 2);
+		assert_eq!(deck.draw1().unwrap_err(), NotEnoughCards { requested: 1, available: 0 });
+		assert_eq!(deck.draw(0), Ok(vec![]));
+
+		assert!(Deal::new(Deck::new(), 8, 7).is_err());
+	}
+
+	#[test]
+	fn invalid_names() {
+		assert_eq!(Rank::try_new("Knight").unwrap_err(), InvalidRank("Knight".to_owned()));
+		assert_eq!(Suit::try_new("cups").unwrap_err().to_string(), "invalid suit: 'cups'");
+		assert_eq!((Rank::new("Ace"), Suit::new("♠")), (Ace, Spades));
+	}
+
+	#[test]
+	fn parsing_cards() {
+		let ace = Card::new(Ace, Some(Spades));
+		for notation in &["A♠", "As", "aS", "A♤", "ace of spades", "Ace Of Spades", " A♠ "] {
+			assert_eq!(notation.parse::<Card>(), Ok(Card::new(Ace, Some(Spades))), "{}", notation);
+		}
+		assert_eq!("10♦".parse(), Ok(Card::new(Ten, Some(Diamonds))));
+		assert_eq!("Td".parse(), Ok(Card::new(Ten, Some(Diamonds))));
+		assert_eq!("ten of diamonds".parse(), Ok(Card::new(Ten, Some(Diamonds))));
+		assert_eq!("Jkr".parse(), Ok(Card::new(Joker, None)));
+		assert_eq!("joker".parse(), Ok(Card::new(Joker, None)));
+		assert_eq!(ace.to_string().parse(), Ok(ace));
+
+		assert_eq!("1s".parse::<Card>(), Err(InvalidRank("1".to_owned())));
+		assert_eq!("Ax".parse::<Card>(), Err(InvalidSuit("x".to_owned())));
+		assert_eq!("A".parse::<Card>(), Err(InvalidCard("A".to_owned())));
+		assert_eq!("joker of hearts".parse::<Card>(), Err(InvalidCard("joker of hearts".to_owned())));
+		assert_eq!("ace of".parse::<Card>().unwrap_err().to_string(), "invalid card: 'ace of'");
+	}
+
+	#[test]
+	fn parsing_hands() {
+		let hand: Hand = "Ah, Td Jkr,queen of clubs  2♥".parse().unwrap();
+		assert_eq!(hand.to_string(), "A♥, 10♦, Jkr, Q♣, 2♥");
+		assert_eq!("".parse::<Hand>().unwrap().size(), 0);
+		assert_eq!("Ah, Tx".parse::<Hand>().unwrap_err(), InvalidSuit("x".to_owned()));
+
+		// every card of a deck survives a round trip
+		let deck = Deck::new_with_joker();
+		assert_eq!(deck.to_string().parse::<Deck>().unwrap().cards, deck.cards);
+
+		let royal = "Jkr Ts Js Qs Ks".parse::<Hand>().unwrap();
+		assert_eq!(royal.evaluate().unwrap().category, Category::RoyalFlush);
+	}
+
+	#[test]
+	fn building_decks() {
+		assert_eq!(Deck::new().size(), 52);
+		assert_eq!(Deck::new_with_joker().size(), 53);
+		assert_eq!(Deck::piquet().size(), 32);
+		assert_eq!(Deck::euchre().size(), 24);
+		assert_eq!(Deck::pinochle().size(), 48);
+		assert_eq!(Deck::shoe(6).size(), 312);
+
+		let euchre = Deck::euchre();
+		assert!(euchre.cards.iter().all(|card| card.rank >= Nine));
+		assert_eq!(Hand { cards: euchre.cards.into_iter().take(6).collect() }.to_string(), "9♠, 10♠, J♠, Q♠, K♠, A♠");
+
+		// every card of a pinochle deck comes twice
+		let pinochle = Deck::pinochle();
+		assert!(pinochle.cards.iter().all(|card| pinochle.cards.iter().filter(|&other| other == card).count() == 2));
+
+		let jokers = DeckBuilder::new().ranks(Ace, Joker).jokers(3).build();
+		assert_eq!(jokers.to_string(), "A♠, A♥, A♦, A♣, Jkr, Jkr, Jkr");
+		assert_eq!(DeckBuilder::new().ranks(King, Queen), DeckBuilder::new().ranks(Queen, King));
+		assert_eq!(DeckBuilder::new().ranks(King, Queen).jokers(2).build().size(), 10);
+		assert_eq!(DeckBuilder::default(), DeckBuilder::new());
+		assert_eq!(DeckBuilder::new().copies(0).build().size(), 0);
+	}
+}